@@ -0,0 +1,75 @@
+//! Loading of a synthetic CSV file into working set, kept in memory and paged to disk.
+//! `CSVRS_BENCH_ROWS` changes number of rows, two million by default.
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use csvrs::index::heap::Storage;
+use csvrs::mem::{Dialect, index_heap_columnar};
+use csvrs::schema::ColumnTypes;
+use std::io::{BufWriter, Write};
+use tempfile::NamedTempFile;
+
+const DEFAULT_ROWS: usize = 2_000_000;
+const CITIES: [&str; 5] = ["NYC", "LA", "Chicago", "Houston", "Phoenix"];
+
+/// Typed, dictionary encoded and plain text columns, with some values missing.
+fn synthetic_csv(rows: usize) -> NamedTempFile {
+    let file = NamedTempFile::new().unwrap();
+    let mut writer = BufWriter::new(file.as_file());
+    writeln!(writer, "id;name;city;score;joined").unwrap();
+    for i in 0..rows {
+        let score = if i % 20 == 0 {
+            String::new()
+        } else {
+            format!("{}.{}", i % 1000, i % 7)
+        };
+        writeln!(
+            writer,
+            "{};name{};{};{};20{:02}-{:02}-{:02}",
+            i,
+            i,
+            CITIES[i % CITIES.len()],
+            score,
+            i % 25,
+            i % 12 + 1,
+            i % 28 + 1
+        )
+        .unwrap();
+    }
+    writer.flush().unwrap();
+    drop(writer);
+    file
+}
+
+fn load(c: &mut Criterion) {
+    let rows = std::env::var("CSVRS_BENCH_ROWS")
+        .ok()
+        .and_then(|rows| rows.parse().ok())
+        .unwrap_or(DEFAULT_ROWS);
+    let file = synthetic_csv(rows);
+
+    let mut group = c.benchmark_group("load");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(rows as u64));
+    for (name, storage) in [
+        ("memory", Storage::Memory),
+        ("paged", Storage::with_memory_limit(64)),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let set = index_heap_columnar(
+                    file.path().to_path_buf(),
+                    &Dialect::default(),
+                    None,
+                    &ColumnTypes::new(),
+                    storage,
+                )
+                .unwrap();
+                assert_eq!(set.rows.len(), rows);
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, load);
+criterion_main!(benches);
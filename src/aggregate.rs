@@ -0,0 +1,282 @@
+/// Aggregate functions computed over filtered rows.
+use std::{borrow::Cow, cmp::Ordering};
+
+use crate::{
+    schema::{ColumnType, decode_float, decode_int},
+    value::parse_number,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum AggregateFunc {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateFunc {
+    pub(crate) fn from_name(name: &str) -> Option<AggregateFunc> {
+        match name.to_lowercase().as_str() {
+            "count" => Some(AggregateFunc::Count),
+            "sum" => Some(AggregateFunc::Sum),
+            "avg" => Some(AggregateFunc::Avg),
+            "min" => Some(AggregateFunc::Min),
+            "max" => Some(AggregateFunc::Max),
+            _ => None,
+        }
+    }
+}
+
+/// Running sum. Stays integral until fractional value shows up or i64 overflows.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn parse(value: &[u8]) -> Option<Number> {
+        let text = str::from_utf8(value).ok()?.trim();
+        if let Ok(i) = text.parse::<i64>() {
+            return Some(Number::Int(i));
+        }
+        parse_number(value).map(Number::Float)
+    }
+
+    /// Number stored in column of given type. Booleans and dates are no numbers.
+    fn of(ty: ColumnType, value: &[u8]) -> Option<Number> {
+        match ty {
+            ColumnType::Int => Some(Number::Int(decode_int(value))),
+            ColumnType::Float => Some(Number::Float(decode_float(value))),
+            ColumnType::Text => Number::parse(value),
+            ColumnType::Bool | ColumnType::Date => None,
+        }
+    }
+
+    fn add(self, other: Number) -> Number {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => a
+                .checked_add(b)
+                .map(Number::Int)
+                .unwrap_or(Number::Float(a as f64 + b as f64)),
+            (a, b) => Number::Float(a.as_f64() + b.as_f64()),
+        }
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Number::Int(i) => i as f64,
+            Number::Float(f) => f,
+        }
+    }
+
+    fn format(self) -> String {
+        match self {
+            Number::Int(i) => i.to_string(),
+            Number::Float(f) => f.to_string(),
+        }
+    }
+}
+
+/// State of a single aggregate while rows are being fed into it. Values are stored ones of
+/// column of given type. Non numeric values are skipped by sum and avg.
+pub(crate) enum Accumulator<'a> {
+    Count(usize),
+    Sum(ColumnType, Option<Number>),
+    Avg(ColumnType, f64, usize),
+    Min(ColumnType, Option<Cow<'a, [u8]>>),
+    Max(ColumnType, Option<Cow<'a, [u8]>>),
+}
+
+impl<'a> Accumulator<'a> {
+    pub(crate) fn new(func: AggregateFunc, ty: ColumnType) -> Accumulator<'a> {
+        match func {
+            AggregateFunc::Count => Accumulator::Count(0),
+            AggregateFunc::Sum => Accumulator::Sum(ty, None),
+            AggregateFunc::Avg => Accumulator::Avg(ty, 0.0, 0),
+            AggregateFunc::Min => Accumulator::Min(ty, None),
+            AggregateFunc::Max => Accumulator::Max(ty, None),
+        }
+    }
+
+    /// Feeds next value. `None` stands for `count(*)`, which has no column to read.
+    pub(crate) fn update(&mut self, value: Option<Cow<'a, [u8]>>) {
+        match (self, value) {
+            (Accumulator::Count(count), _) => *count += 1,
+            (Accumulator::Sum(ty, sum), Some(value)) => {
+                if let Some(n) = Number::of(*ty, &value) {
+                    *sum = Some(sum.map_or(n, |s| s.add(n)));
+                }
+            }
+            (Accumulator::Avg(ty, sum, count), Some(value)) => {
+                if let Some(n) = Number::of(*ty, &value) {
+                    *sum += n.as_f64();
+                    *count += 1;
+                }
+            }
+            (Accumulator::Min(ty, min), Some(value)) => {
+                if min
+                    .as_ref()
+                    .is_none_or(|m| ty.compare(&value, m) == Ordering::Less)
+                {
+                    *min = Some(value);
+                }
+            }
+            (Accumulator::Max(ty, max), Some(value)) => {
+                if max
+                    .as_ref()
+                    .is_none_or(|m| ty.compare(&value, m) == Ordering::Greater)
+                {
+                    *max = Some(value);
+                }
+            }
+            (_, None) => panic!("Only count accepts *"),
+        }
+    }
+
+    /// Final value of the aggregate as text. `None` (null) when there was nothing to aggregate.
+    pub(crate) fn finish(&self) -> Option<Vec<u8>> {
+        match self {
+            Accumulator::Count(count) => Some(count.to_string().into_bytes()),
+            Accumulator::Sum(_, sum) => sum.map(|sum| sum.format().into_bytes()),
+            Accumulator::Avg(_, _, 0) => None,
+            Accumulator::Avg(_, sum, count) => Some((sum / *count as f64).to_string().into_bytes()),
+            Accumulator::Min(ty, value) | Accumulator::Max(ty, value) => value
+                .as_ref()
+                .map(|value| ty.format(Cow::Borrowed(value)).into_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Result of aggregate, `null` when there was nothing to aggregate.
+    fn result(acc: &Accumulator) -> Vec<u8> {
+        acc.finish().unwrap_or_else(|| b"null".to_vec())
+    }
+
+    fn aggregate(func: AggregateFunc, values: &[&'static [u8]]) -> Vec<u8> {
+        let mut acc = Accumulator::new(func, ColumnType::Text);
+        for value in values {
+            acc.update(Some(Cow::Borrowed(value)));
+        }
+        result(&acc)
+    }
+
+    #[test]
+    fn test_count() {
+        assert_eq!(aggregate(AggregateFunc::Count, &[b"a", b"", b"c"]), b"3");
+    }
+
+    #[test]
+    fn test_count_star() {
+        let mut acc = Accumulator::new(AggregateFunc::Count, ColumnType::Text);
+        acc.update(None);
+        acc.update(None);
+        assert_eq!(result(&acc), b"2");
+    }
+
+    #[test]
+    fn test_sum_stays_integral() {
+        assert_eq!(aggregate(AggregateFunc::Sum, &[b"1", b"2", b"39"]), b"42");
+    }
+
+    #[test]
+    fn test_sum_switches_to_float() {
+        assert_eq!(aggregate(AggregateFunc::Sum, &[b"1", b"0.5"]), b"1.5");
+    }
+
+    #[test]
+    fn test_sum_skips_non_numeric() {
+        assert_eq!(aggregate(AggregateFunc::Sum, &[b"1", b"n/a", b"2"]), b"3");
+        assert_eq!(aggregate(AggregateFunc::Sum, &[b"n/a"]), b"null");
+    }
+
+    #[test]
+    fn test_avg() {
+        assert_eq!(aggregate(AggregateFunc::Avg, &[b"1", b"2"]), b"1.5");
+        assert_eq!(aggregate(AggregateFunc::Avg, &[]), b"null");
+    }
+
+    #[test]
+    fn test_min_max_are_numeric_aware() {
+        let values: [&[u8]; 3] = [b"9", b"10", b"100"];
+        assert_eq!(aggregate(AggregateFunc::Min, &values), b"9");
+        assert_eq!(aggregate(AggregateFunc::Max, &values), b"100");
+    }
+
+    #[test]
+    fn test_nothing_to_aggregate_is_null() {
+        for func in [AggregateFunc::Sum, AggregateFunc::Min, AggregateFunc::Max] {
+            assert_eq!(aggregate(func, &[]), b"null");
+        }
+        assert_eq!(aggregate(AggregateFunc::Count, &[]), b"0");
+    }
+
+    #[test]
+    fn test_min_max_text() {
+        let values: [&[u8]; 3] = [b"NYC", b"Chicago", b"LA"];
+        assert_eq!(aggregate(AggregateFunc::Min, &values), b"Chicago");
+        assert_eq!(aggregate(AggregateFunc::Max, &values), b"NYC");
+    }
+
+    fn aggregate_typed(func: AggregateFunc, ty: ColumnType, values: &[&str]) -> Vec<u8> {
+        let encoded: Vec<Vec<u8>> = values
+            .iter()
+            .map(|v| ty.parse(v.as_bytes()).unwrap())
+            .collect();
+        let mut acc = Accumulator::new(func, ty);
+        for value in &encoded {
+            acc.update(Some(Cow::Borrowed(value)));
+        }
+        result(&acc)
+    }
+
+    #[test]
+    fn test_aggregate_typed_values() {
+        let ints = ["7", "-2", "40"];
+        assert_eq!(
+            aggregate_typed(AggregateFunc::Sum, ColumnType::Int, &ints),
+            b"45"
+        );
+        assert_eq!(
+            aggregate_typed(AggregateFunc::Avg, ColumnType::Int, &ints),
+            b"15"
+        );
+        assert_eq!(
+            aggregate_typed(AggregateFunc::Min, ColumnType::Int, &ints),
+            b"-2"
+        );
+
+        let floats = ["0.5", "1e3"];
+        assert_eq!(
+            aggregate_typed(AggregateFunc::Sum, ColumnType::Float, &floats),
+            b"1000.5"
+        );
+        assert_eq!(
+            aggregate_typed(AggregateFunc::Max, ColumnType::Float, &floats),
+            b"1000"
+        );
+    }
+
+    #[test]
+    fn test_aggregate_dates() {
+        let dates = ["2024-03-01", "1999-12-31", "2024-02-29"];
+        assert_eq!(
+            aggregate_typed(AggregateFunc::Min, ColumnType::Date, &dates),
+            b"1999-12-31"
+        );
+        assert_eq!(
+            aggregate_typed(AggregateFunc::Max, ColumnType::Date, &dates),
+            b"2024-03-01"
+        );
+        // Dates are no numbers
+        assert_eq!(
+            aggregate_typed(AggregateFunc::Sum, ColumnType::Date, &dates),
+            b"null"
+        );
+    }
+}
@@ -1,153 +1,2451 @@
 use core::panic;
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    convert::Infallible,
+    ops::Bound,
+};
 
 use crate::{
-    index::heap::BlockOffset,
-    mem::{ColumnsWorkingSet, read_columnar},
-    query::parser::{self, Expr, Visitor},
+    aggregate::{Accumulator, AggregateFunc},
+    index::{ColumnIndex, IndexKind, bitmap::Bitmap, heap::fixed_rows, tree::BTree},
+    mem::{Catalog, ColumnsWorkingSet, decode_code},
+    query::parser::{self, Clauses, Expr, OrderKey, Visitor},
     query::token::TokenType,
+    schema::ColumnType,
+    value::{TEXT_KEY, append_key, parse_number, tuple_key_end},
 };
 
 pub(crate) struct ColumnarExecutor<'a> {
     pub(crate) set: &'a ColumnsWorkingSet,
 }
 
-type RowResult = Vec<Vec<(usize, (usize, BlockOffset))>>;
+/// Runs statements against table selected with `@ table`.
+pub struct CatalogExecutor<'a> {
+    pub catalog: &'a Catalog,
+}
+
+/// Rows of values returned by query. Values are borrowed from working set, unless they were
+/// computed by the query itself (e.g. aggregates).
+pub type Rows<'a> = Vec<Vec<Cow<'a, [u8]>>>;
+
+pub type RowResult<'a> = Result<Rows<'a>, ExecutionError>;
+
+type ExecutionResult<T> = Result<T, ExecutionError>;
+
+/// Statement that parsed, but can't be run against the table, e.g. because of unknown column.
+#[derive(Debug, PartialEq)]
+pub struct ExecutionError(pub(crate) String);
+
+impl std::error::Error for ExecutionError {}
+
+impl std::fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Execution Error: {}", self.0)
+    }
+}
+
+/// Invalid request, or values that can't be read from their heap.
+impl From<std::io::Error> for ExecutionError {
+    fn from(err: std::io::Error) -> Self {
+        if err.kind() == std::io::ErrorKind::InvalidInput {
+            ExecutionError(err.to_string())
+        } else {
+            ExecutionError(format!("Failed to read values: {}", err))
+        }
+    }
+}
+
+/// Values of group by columns identifying single group, `None` for null.
+type GroupKey<'a> = Vec<Option<Cow<'a, [u8]>>>;
+
+type Predicate = Box<dyn Fn(&[u8]) -> bool>;
 
 /// Type after parsing where statement. It returns (usize, PredicateFunc).
-/// First usize is id of BufferPool for given column
-type RowFilter = (usize, Box<dyn Fn(&[u8]) -> bool>);
+/// First usize is slot of the compared value: id of column when filtering rows, or position in
+/// grouped row when filtering groups.
+type RowFilter = (usize, Predicate);
+
+/// Maps left hand side of comparison to the slot it reads and type of values stored there.
+type SlotResolver<'r> = &'r dyn Fn(&Expr) -> ExecutionResult<(usize, ColumnType)>;
 
 #[derive(Clone, Copy, Debug)]
 enum LogicalOp {
     And,
     Or,
-    None, // First condition has no preceding operator
 }
 
-type CompoundFilter = Vec<(LogicalOp, RowFilter)>;
+/// Filter tree built from where clause. Mirrors the shape of parsed conditional expression.
+enum Filter {
+    Comparison(RowFilter),
+    /// Whether the slot is null, or not null when negated
+    IsNull(usize, bool),
+    Logical(Box<Filter>, LogicalOp, Box<Filter>),
+    Not(Box<Filter>),
+}
 
 impl ColumnarExecutor<'_> {
-    fn build_filters(&self, cond: &Expr) -> CompoundFilter {
-        let mut filters = Vec::new();
-        self.collect_filters(cond, LogicalOp::None, &mut filters);
-        filters
+    fn build_filter(&self, cond: &Expr, resolve: SlotResolver) -> ExecutionResult<Filter> {
+        let filter = match cond {
+            Expr::Conditional(left, token, right) => match token.t {
+                TokenType::And | TokenType::Or => {
+                    let op = if token.t == TokenType::And {
+                        LogicalOp::And
+                    } else {
+                        LogicalOp::Or
+                    };
+                    Filter::Logical(
+                        Box::new(self.build_filter(left, resolve)?),
+                        op,
+                        Box::new(self.build_filter(right, resolve)?),
+                    )
+                }
+                TokenType::Equals
+                | TokenType::NotEquals
+                | TokenType::Less
+                | TokenType::LessEquals
+                | TokenType::Greater
+                | TokenType::GreaterEquals => {
+                    // Leaf comparison: col <op> value
+                    let (slot, ty) = resolve(left)?;
+                    Filter::Comparison(self.build_comparison(slot, ty, &token.t, right))
+                }
+                _ => panic!("Unexpected operator in conditional: {:?}", token.t),
+            },
+            Expr::Unary(token, right) if token.t == TokenType::Bang => {
+                Filter::Not(Box::new(self.build_filter(right, resolve)?))
+            }
+            Expr::IsNull(left, negated) => Filter::IsNull(resolve(left)?.0, *negated),
+            _ => panic!("Expected conditional"),
+        };
+        Ok(filter)
+    }
+
+    /// Builds filter for where clause, which can only reference plain columns. It is
+    /// evaluated on stored bytes, see [`ColumnsWorkingSet::stored`].
+    fn build_where_filter(&self, cond: &Expr) -> ExecutionResult<Filter> {
+        let filter = self.build_filter(cond, &|left| match left {
+            Expr::Call(..) => Err(ExecutionError(
+                "Aggregates are not allowed in where clause".to_string(),
+            )),
+            _ => {
+                let column = self.column_id(left)?;
+                Ok((column, self.set.types[column]))
+            }
+        })?;
+        self.compare_codes(filter)
+    }
+
+    /// Comparisons of dictionary encoded columns are evaluated once for every distinct value,
+    /// rows then only look up result by their code.
+    fn compare_codes(&self, filter: Filter) -> ExecutionResult<Filter> {
+        let filter = match filter {
+            Filter::Comparison((column, predicate)) => match &self.set.dictionaries[column] {
+                Some(dictionary) => {
+                    let matches = dictionary
+                        .values()
+                        .map(|v| Ok(predicate(&v?)))
+                        .collect::<std::io::Result<Vec<bool>>>()?;
+                    Filter::Comparison((column, Box::new(move |code| matches[decode_code(code)])))
+                }
+                None => Filter::Comparison((column, predicate)),
+            },
+            Filter::Logical(left, op, right) => Filter::Logical(
+                Box::new(self.compare_codes(*left)?),
+                op,
+                Box::new(self.compare_codes(*right)?),
+            ),
+            Filter::Not(inner) => Filter::Not(Box::new(self.compare_codes(*inner)?)),
+            Filter::IsNull(..) => filter,
+        };
+        Ok(filter)
+    }
+
+    /// Builds predicate for single `slot <op> value` comparison of values of given type.
+    /// Value parsing as that type is compared by type. Otherwise values are compared as text:
+    /// equality operators compare raw bytes, range operators compare numerically when both
+    /// sides parse as numbers and lexicographically otherwise.
+    fn build_comparison(
+        &self,
+        slot: usize,
+        ty: ColumnType,
+        op: &TokenType,
+        right: &Expr,
+    ) -> RowFilter {
+        let expected = parse_term(right).into_bytes();
+        let accepts: fn(Ordering) -> bool = match op {
+            TokenType::Equals => Ordering::is_eq,
+            TokenType::NotEquals => Ordering::is_ne,
+            TokenType::Less => Ordering::is_lt,
+            TokenType::LessEquals => Ordering::is_le,
+            TokenType::Greater => Ordering::is_gt,
+            TokenType::GreaterEquals => Ordering::is_ge,
+            _ => panic!("Unexpected comparison operator: {:?}", op),
+        };
+
+        if ty != ColumnType::Text
+            && let Some(expected) = ty.parse(&expected)
+        {
+            return (
+                slot,
+                Box::new(move |actual| accepts(ty.compare(actual, &expected))),
+            );
+        }
+
+        let equality = matches!(op, TokenType::Equals | TokenType::NotEquals);
+        let expected_number = parse_number(&expected);
+        let predicate: Predicate = Box::new(move |actual| {
+            let actual = ty.format(Cow::Borrowed(actual));
+            let ordering = match (parse_number(&actual), expected_number) {
+                (Some(a), Some(e)) if !equality => a.partial_cmp(&e),
+                _ => Some((*actual).cmp(expected.as_slice())),
+            };
+            ordering.is_some_and(accepts)
+        });
+
+        (slot, predicate)
+    }
+
+    fn evaluate_filter(&self, filter: &Filter, row: usize) -> ExecutionResult<bool> {
+        Ok(evaluate_filter(filter, &|col| self.set.stored(row, col))? == Some(true))
+    }
+
+    /// Literal compared with column, in the form column values take in indexes: encoded
+    /// for hash and bitmap, `None` when it is no value of column type, and text for B-tree.
+    fn literal(&self, column: usize, right: &Expr) -> (Option<Vec<u8>>, Vec<u8>) {
+        let ty = self.set.types[column];
+        let literal = parse_term(right).into_bytes();
+        match ty.parse(&literal) {
+            Some(encoded) => (
+                Some(encoded.clone()),
+                ty.format(Cow::Owned(encoded)).into_owned(),
+            ),
+            None => (None, literal),
+        }
+    }
+}
+
+fn parse_term(term: &Expr) -> String {
+    if let Expr::Literal(token) = term {
+        return token.literal.to_string();
+    }
+    panic!("Invalid token");
+}
+
+/// Evaluates filter tree in three valued logic. `value` returns value stored in given slot of
+/// current row, `None` for null. Comparison with null is unknown (`None`), so is its negation,
+/// only rows evaluating to `Some(true)` match.
+fn evaluate_filter<'v, E>(
+    filter: &Filter,
+    value: &impl Fn(usize) -> Result<Option<Cow<'v, [u8]>>, E>,
+) -> Result<Option<bool>, E> {
+    let result = match filter {
+        Filter::Comparison((slot, predicate)) => value(*slot)?.map(|v| predicate(&v)),
+        Filter::IsNull(slot, negated) => Some(value(*slot)?.is_none() != *negated),
+        Filter::Logical(left, op, right) => {
+            // False decides conjunction and true disjunction even when the other side is unknown
+            let decisive = Some(matches!(op, LogicalOp::Or));
+            let left = evaluate_filter(left, value)?;
+            if left == decisive {
+                return Ok(left);
+            }
+            let right = evaluate_filter(right, value)?;
+            if right == decisive {
+                return Ok(right);
+            }
+            left.and(right)
+        }
+        Filter::Not(inner) => evaluate_filter(inner, value)?.map(|matches| !matches),
+    };
+    Ok(result)
+}
+
+/// Single item of `get` column list.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Projection {
+    Column(usize),
+    /// Aggregate over a column, `None` column stands for `*`
+    Aggregate(AggregateFunc, Option<usize>),
+}
+
+/// Shape of rows produced by grouping: group by columns followed by aggregates.
+struct GroupLayout {
+    keys: Vec<usize>,
+    aggregates: Vec<(AggregateFunc, Option<usize>)>,
+}
+
+impl GroupLayout {
+    fn add_aggregate(&mut self, projection: Projection) {
+        if let Projection::Aggregate(func, column) = projection
+            && !self.aggregates.contains(&(func, column))
+        {
+            self.aggregates.push((func, column));
+        }
     }
 
-    fn collect_filters(&self, cond: &Expr, op: LogicalOp, filters: &mut CompoundFilter) {
+    /// Position of projected value in grouped row.
+    fn slot(&self, projection: &Projection) -> ExecutionResult<usize> {
+        match projection {
+            Projection::Column(col) => {
+                self.keys.iter().position(|key| key == col).ok_or_else(|| {
+                    ExecutionError(
+                        "Column must appear in group by clause or be used in aggregate".to_string(),
+                    )
+                })
+            }
+            Projection::Aggregate(func, column) => {
+                let i = self
+                    .aggregates
+                    .iter()
+                    .position(|agg| *agg == (*func, *column))
+                    .expect("Missing aggregate");
+                Ok(self.keys.len() + i)
+            }
+        }
+    }
+}
+
+impl<'a> ColumnarExecutor<'a> {
+    fn build_projections(&self, expr: &Expr) -> ExecutionResult<Vec<Projection>> {
+        match expr {
+            Expr::Multiple(left, right) => {
+                let mut projections = self.build_projections(left)?;
+                projections.extend(self.build_projections(right)?);
+                Ok(projections)
+            }
+            _ => Ok(vec![self.build_projection(expr)?]),
+        }
+    }
+
+    fn build_projection(&self, expr: &Expr) -> ExecutionResult<Projection> {
+        match expr {
+            Expr::Literal(_) => Ok(Projection::Column(self.column_id(expr)?)),
+            Expr::Call(function, argument) => {
+                let func = AggregateFunc::from_name(&function.literal.to_string())
+                    .expect("Unknown aggregate function");
+                let column = match argument.as_ref() {
+                    Expr::Literal(token) if token.t == TokenType::Star => None,
+                    _ => Some(self.column_id(argument)?),
+                };
+                Ok(Projection::Aggregate(func, column))
+            }
+            _ => panic!("Invalid syntax"),
+        }
+    }
+
+    /// Collects aggregates used by conditional expression (e.g. having clause).
+    fn collect_aggregates(&self, cond: &Expr, layout: &mut GroupLayout) -> ExecutionResult<()> {
         match cond {
-            Expr::Conditional(left, token, right) => {
-                match token.t {
-                    TokenType::And | TokenType::Or => {
-                        // Nested conditional - recurse into both sides
-                        self.collect_filters(left, op, filters);
-                        let next_op = if token.t == TokenType::And {
-                            LogicalOp::And
-                        } else {
-                            LogicalOp::Or
-                        };
-                        self.collect_filters(right, next_op, filters);
+            Expr::Call(..) => layout.add_aggregate(self.build_projection(cond)?),
+            Expr::Conditional(left, _, right) => {
+                self.collect_aggregates(left, layout)?;
+                self.collect_aggregates(right, layout)?;
+            }
+            Expr::Unary(_, right) | Expr::IsNull(right, _) => {
+                self.collect_aggregates(right, layout)?
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn column_id(&self, term: &Expr) -> ExecutionResult<usize> {
+        let col = parse_term(term);
+        match self.set.columns.get(&col) {
+            Some(id) => Ok(*id),
+            None => Err(ExecutionError(format!("Missing column {}", col))),
+        }
+    }
+
+    fn column_ids(&self, terms: &Expr) -> ExecutionResult<Vec<usize>> {
+        match terms {
+            Expr::Multiple(left, right) => {
+                let mut ids = self.column_ids(left)?;
+                ids.extend(self.column_ids(right)?);
+                Ok(ids)
+            }
+            _ => Ok(vec![self.column_id(terms)?]),
+        }
+    }
+
+    /// Hash based grouping. Groups are keyed by stored values of group by columns, borrowed
+    /// from working set unless they had to be reassembled from overflow blocks. Without group
+    /// by clause all rows fall into single group.
+    /// Nulls form a group of their own and are skipped by aggregates, except `count(*)`.
+    fn group(
+        &self,
+        projections: &[Projection],
+        clauses: &Clauses,
+        rows: impl Iterator<Item = ExecutionResult<usize>>,
+    ) -> RowResult<'a> {
+        let set: &'a ColumnsWorkingSet = self.set;
+
+        let mut layout = GroupLayout {
+            keys: vec![],
+            aggregates: vec![],
+        };
+        if let Some(group_by) = &clauses.group_by {
+            layout.keys = self
+                .build_projections(group_by)?
+                .iter()
+                .map(|p| match p {
+                    Projection::Column(col) => Ok(*col),
+                    Projection::Aggregate(..) => {
+                        Err(ExecutionError("Cannot group by aggregate".to_string()))
                     }
-                    TokenType::Equals => {
-                        // Leaf comparison: col = value
-                        let filter = self.build_comparison(left, right);
-                        filters.push((op, filter));
+                })
+                .collect::<ExecutionResult<_>>()?;
+        }
+        for projection in projections {
+            layout.add_aggregate(*projection);
+        }
+        if let Some(having) = &clauses.having {
+            self.collect_aggregates(having, &mut layout)?;
+        }
+        for key in &clauses.order_by {
+            self.collect_aggregates(&key.expr, &mut layout)?;
+        }
+
+        // Group keys keep type of their columns, aggregates are computed as text
+        let types: Vec<ColumnType> = layout
+            .keys
+            .iter()
+            .map(|col| set.types[*col])
+            .chain(layout.aggregates.iter().map(|_| ColumnType::Text))
+            .collect();
+
+        let new_accumulators = || -> Vec<Accumulator<'a>> {
+            layout
+                .aggregates
+                .iter()
+                .map(|(func, column)| {
+                    Accumulator::new(*func, column.map_or(ColumnType::Text, |c| set.types[c]))
+                })
+                .collect()
+        };
+
+        let mut index: HashMap<GroupKey<'a>, usize> = HashMap::new();
+        let mut groups: Vec<(GroupKey<'a>, Vec<Accumulator<'a>>)> = vec![];
+        for row in rows {
+            let row = row?;
+            let key = layout
+                .keys
+                .iter()
+                .map(|&col| set.value(row, col))
+                .collect::<std::io::Result<GroupKey<'a>>>()?;
+
+            let group_id = *index.entry(key).or_insert_with_key(|key| {
+                groups.push((key.clone(), new_accumulators()));
+                groups.len() - 1
+            });
+
+            let accumulators = &mut groups[group_id].1;
+            for (acc, (_, column)) in accumulators.iter_mut().zip(&layout.aggregates) {
+                match column {
+                    Some(col) => {
+                        if let Some(value) = set.value(row, *col)? {
+                            acc.update(Some(value));
+                        }
                     }
-                    _ => panic!("Unexpected operator in conditional: {:?}", token.t),
+                    None => acc.update(None),
                 }
             }
-            _ => panic!("Expected conditional"),
         }
+
+        if groups.is_empty() && layout.keys.is_empty() {
+            groups.push((vec![], new_accumulators()));
+        }
+
+        let having = clauses
+            .having
+            .as_ref()
+            .map(|cond| {
+                self.build_filter(cond, &|left| {
+                    let slot = layout.slot(&self.build_projection(left)?)?;
+                    Ok((slot, types[slot]))
+                })
+            })
+            .transpose()?;
+        let slots: Vec<usize> = projections
+            .iter()
+            .map(|p| layout.slot(p))
+            .collect::<ExecutionResult<_>>()?;
+
+        let grouped = groups
+            .into_iter()
+            .map(|(key, accumulators)| -> GroupKey<'a> {
+                key.into_iter()
+                    .chain(accumulators.iter().map(|acc| acc.finish().map(Cow::Owned)))
+                    .collect()
+            })
+            .filter(|row| {
+                having.as_ref().is_none_or(|f| {
+                    evaluate_filter(f, &|slot| {
+                        Ok::<_, Infallible>(row[slot].as_deref().map(Cow::Borrowed))
+                    }) == Ok(Some(true))
+                })
+            });
+
+        let grouped: Vec<GroupKey<'a>> = if clauses.order_by.is_empty() {
+            grouped.collect()
+        } else {
+            let keys: Vec<SortKey> = clauses
+                .order_by
+                .iter()
+                .map(|key| {
+                    let slot = layout.slot(&self.build_projection(&key.expr)?)?;
+                    Ok((slot, types[slot], key.descending))
+                })
+                .collect::<ExecutionResult<_>>()?;
+            let Ok(sorted) = sort(grouped.map(Ok::<_, Infallible>), window(clauses), |a, b| {
+                compare_rows(&keys, a, b)
+            });
+            sorted
+        };
+
+        Ok(grouped
+            .into_iter()
+            .skip(clauses.offset)
+            .take(clauses.limit.unwrap_or(usize::MAX))
+            .map(|row| {
+                slots
+                    .iter()
+                    .map(|slot| match &row[*slot] {
+                        Some(value) => types[*slot].format(value.clone()),
+                        None => Cow::Borrowed(&[][..]),
+                    })
+                    .collect()
+            })
+            .collect())
     }
 
-    fn build_comparison(&self, left: &Expr, right: &Expr) -> RowFilter {
-        let col_name = self.parse_term(left);
-        let col_idx = *self
-            .set
-            .columns
-            .get(&col_name)
-            .expect("Missing filter column");
+    /// Sorts matching rows by order by keys. Sort values are read once per row.
+    /// With `window` only that many first rows are kept.
+    fn sort_rows(
+        &self,
+        rows: impl Iterator<Item = ExecutionResult<usize>>,
+        order_by: &[OrderKey],
+        window: Option<usize>,
+    ) -> ExecutionResult<Vec<usize>> {
+        let set: &'a ColumnsWorkingSet = self.set;
+        let columns: Vec<usize> = order_by
+            .iter()
+            .map(|key| match &key.expr {
+                Expr::Call(..) => Err(ExecutionError(
+                    "Cannot order by aggregate without grouping".to_string(),
+                )),
+                expr => self.column_id(expr),
+            })
+            .collect::<ExecutionResult<_>>()?;
+        let keys: Vec<SortKey> = order_by
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (i, set.types[columns[i]], key.descending))
+            .collect();
 
-        let expected = self.parse_term(right);
-        let expected_bytes = expected.into_bytes();
+        let keyed = rows.map(|row| -> ExecutionResult<_> {
+            let row = row?;
+            let values = columns
+                .iter()
+                .map(|&col| set.value(row, col))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            Ok((values, row))
+        });
 
-        (
-            col_idx,
-            Box::new(move |actual: &[u8]| actual == expected_bytes.as_slice()),
-        )
+        Ok(sort(keyed, window, |a, b| compare_rows(&keys, &a.0, &b.0))?
+            .into_iter()
+            .map(|(_, row)| row)
+            .collect())
     }
 
-    fn parse_term(&self, term: &Expr) -> String {
-        if let Expr::Literal(token) = term {
-            return token.literal.to_string();
+    fn project(
+        &self,
+        projections: &[Projection],
+        row: usize,
+    ) -> ExecutionResult<Vec<Cow<'a, [u8]>>> {
+        let set: &'a ColumnsWorkingSet = self.set;
+        let values = projections
+            .iter()
+            .map(|p| match p {
+                Projection::Column(i) => set.text(row, *i).map(Option::unwrap_or_default),
+                Projection::Aggregate(..) => unreachable!(),
+            })
+            .collect::<std::io::Result<_>>()?;
+        Ok(values)
+    }
+}
+
+/// Number of leading rows needed to answer the query, offset included. `None` means all of them.
+fn window(clauses: &Clauses) -> Option<usize> {
+    clauses
+        .limit
+        .map(|limit| limit.saturating_add(clauses.offset))
+}
+
+/// Stable sort. With `window` it keeps only that many smallest items in bounded max-heap,
+/// instead of sorting everything. Stops at the first item that failed to be read.
+fn sort<T, E>(
+    items: impl Iterator<Item = Result<T, E>>,
+    window: Option<usize>,
+    cmp: impl Fn(&T, &T) -> Ordering,
+) -> Result<Vec<T>, E> {
+    let Some(n) = window else {
+        let mut items = items.collect::<Result<Vec<T>, E>>()?;
+        items.sort_by(cmp);
+        return Ok(items);
+    };
+
+    /// Heap entry. Ties are broken by input position, so result matches stable sort.
+    struct Entry<'c, T> {
+        item: T,
+        seq: usize,
+        cmp: &'c dyn Fn(&T, &T) -> Ordering,
+    }
+
+    impl<T> Ord for Entry<'_, T> {
+        fn cmp(&self, other: &Self) -> Ordering {
+            (self.cmp)(&self.item, &other.item).then(self.seq.cmp(&other.seq))
+        }
+    }
+
+    impl<T> PartialOrd for Entry<'_, T> {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
         }
-        panic!("Invalid token");
     }
 
-    fn evaluate_filters(&self, filters: &CompoundFilter, row: &[(usize, BlockOffset)]) -> bool {
-        let mut result = true;
+    impl<T> PartialEq for Entry<'_, T> {
+        fn eq(&self, other: &Self) -> bool {
+            self.cmp(other) == Ordering::Equal
+        }
+    }
 
-        for (op, (col_idx, predicate)) in filters {
-            let (block_id, offset) = row[*col_idx];
-            let actual_bytes = read_columnar(self.set, *col_idx, (block_id, offset));
-            let matches = predicate(actual_bytes);
+    impl<T> Eq for Entry<'_, T> {}
 
-            match op {
-                LogicalOp::None => result = matches,
-                LogicalOp::And => result = result && matches,
-                LogicalOp::Or => result = result || matches,
-            }
+    if n == 0 {
+        return Ok(vec![]);
+    }
+
+    // Window may be far larger than the input, e.g. `limit` of huge number
+    let mut heap = BinaryHeap::with_capacity(n.min(items.size_hint().0));
+    for (seq, item) in items.enumerate() {
+        let entry = Entry {
+            item: item?,
+            seq,
+            cmp: &cmp,
+        };
+
+        if heap.len() < n {
+            heap.push(entry);
+        } else if let Some(mut largest) = heap.peek_mut()
+            && entry < *largest
+        {
+            *largest = entry;
         }
+    }
+
+    Ok(heap
+        .into_sorted_vec()
+        .into_iter()
+        .map(|entry| entry.item)
+        .collect())
+}
+
+/// Slot of sort value, its type and whether it is sorted in descending order.
+type SortKey = (usize, ColumnType, bool);
 
-        result
+/// Compares two rows by sort keys. Values are compared by type, see `ColumnType::compare`.
+/// Nulls come after all values, so they are last in ascending order and first in descending.
+fn compare_rows<V: AsRef<[u8]>>(keys: &[SortKey], a: &[Option<V>], b: &[Option<V>]) -> Ordering {
+    for &(slot, ty, descending) in keys {
+        let ordering = match (&a[slot], &b[slot]) {
+            (Some(a), Some(b)) => ty.compare(a.as_ref(), b.as_ref()),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        };
+        let ordering = if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
     }
+    Ordering::Equal
 }
 
-impl Visitor<RowResult> for ColumnarExecutor<'_> {
-    fn visit(&self, expr: &parser::Statement) -> RowResult {
-        match expr {
-            parser::Statement::Get(expr, _table, conditions) => {
-                let get_columns = get_column_names(expr);
+/// How rows matching where clause are found.
+#[derive(Debug, PartialEq)]
+pub enum AccessPath {
+    FullScan,
+    /// Rows are looked up in indexes of given columns
+    IndexScan(Vec<String>),
+}
 
-                let selected_cols_ids: Vec<usize> = get_columns
-                    .iter()
-                    .map(|col| {
-                        let val = self.set.columns.get(col).expect("Missing column");
-                        *val
-                    })
-                    .collect();
+impl std::fmt::Display for AccessPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccessPath::FullScan => write!(f, "full scan"),
+            AccessPath::IndexScan(columns) => write!(f, "index scan on {}", columns.join(", ")),
+        }
+    }
+}
+
+type KeyRange = (Bound<Vec<u8>>, Bound<Vec<u8>>);
+type ZonePredicate = Box<dyn Fn(&[u8], &[u8]) -> bool>;
+type Indexes = [(Vec<usize>, ColumnIndex)];
 
-                let filters = conditions.as_ref().map(|c| self.build_filters(c));
+/// Part of where clause answered by indexes. Rows it yields are superset of matching rows,
+/// the whole where filter is still evaluated on them unless the plan is exact.
+/// Indexes are referred to by their position in working set.
+enum IndexPlan {
+    /// Rows with B-tree key of the column in any of the ranges
+    Range(usize, Vec<KeyRange>),
+    /// Rows holding exactly given value, looked up in hash or bitmap index
+    Equals(usize, Vec<u8>),
+    /// Rows whose value satisfies the predicate, looked up in bitmap index
+    Matching(usize, Predicate),
+    /// Rows of blocks of the column whose zone (smallest and largest value) is accepted
+    Blocks(usize, ZonePredicate),
+    Logical(Box<IndexPlan>, LogicalOp, Box<IndexPlan>),
+}
+
+/// Key ranges of rows that start with tuple key `prefix` and then satisfy `column <op> expected`.
+/// Numbers and texts compare with each other lexicographically, so range operators take the
+/// whole other class of values too.
+fn key_ranges(prefix: &[u8], op: &TokenType, expected: &[u8]) -> Option<Vec<KeyRange>> {
+    let mut key = prefix.to_vec();
+    append_key(&mut key, expected);
+    let key_end = tuple_key_end(&key);
+
+    // All keys starting with prefix
+    let (first, last) = if prefix.is_empty() {
+        (Bound::Unbounded, Bound::Unbounded)
+    } else {
+        (
+            Bound::Included(prefix.to_vec()),
+            Bound::Excluded(tuple_key_end(prefix)),
+        )
+    };
+    let mut text = prefix.to_vec();
+    text.push(TEXT_KEY);
+    let other_class = if parse_number(expected).is_some() {
+        (Bound::Included(text), last.clone())
+    } else {
+        (first.clone(), Bound::Excluded(text))
+    };
+
+    let range = match op {
+        TokenType::Equals => {
+            return Some(vec![(Bound::Included(key), Bound::Excluded(key_end))]);
+        }
+        TokenType::Less => (first, Bound::Excluded(key)),
+        TokenType::LessEquals => (first, Bound::Excluded(key_end)),
+        TokenType::Greater => (Bound::Included(key_end), last),
+        TokenType::GreaterEquals => (Bound::Included(key), last),
+        _ => return None,
+    };
+    Some(vec![range, other_class])
+}
+
+/// Whether block with values between `min` and `max` may hold value satisfying
+/// `value <op> expected`. Values are compared by type.
+fn zone_accepts(ty: ColumnType, op: &TokenType, expected: Vec<u8>) -> Option<ZonePredicate> {
+    let compare = move |value: &[u8]| ty.compare(value, &expected);
+    let accepts: ZonePredicate = match op {
+        TokenType::Equals => Box::new(move |min, max| compare(min).is_le() && compare(max).is_ge()),
+        TokenType::NotEquals => {
+            Box::new(move |min, max| !compare(min).is_eq() || !compare(max).is_eq())
+        }
+        TokenType::Less => Box::new(move |min, _| compare(min).is_lt()),
+        TokenType::LessEquals => Box::new(move |min, _| compare(min).is_le()),
+        TokenType::Greater => Box::new(move |_, max| compare(max).is_gt()),
+        TokenType::GreaterEquals => Box::new(move |_, max| compare(max).is_ge()),
+        _ => return None,
+    };
+    Some(accepts)
+}
 
-                let mut res = vec![];
-                for row in &self.set.rows {
-                    // Apply WHERE filters
-                    if let Some(ref compound_filter) = filters
-                        && !self.evaluate_filters(compound_filter, row)
+/// Rows found under given key ranges.
+fn lookup_rows(tree: &BTree, ranges: &[KeyRange]) -> Bitmap {
+    ranges
+        .iter()
+        .flat_map(|(start, end)| {
+            tree.range(
+                start.as_ref().map(Vec::as_slice),
+                end.as_ref().map(Vec::as_slice),
+            )
+            .map(|(_, tid)| tid.row as u32)
+        })
+        .collect()
+}
+
+impl<'a> ColumnarExecutor<'a> {
+    /// Finds parts of where clause that can be answered by indexes. Conjunction needs index on
+    /// either side, disjunction on both of them. Negations are never looked up. Comparison of
+    /// column without index still skips blocks by zone map of the column, when it has more
+    /// than one block and the value is compared by type.
+    /// Returns plan and whether its rows are exactly the matching ones, which holds when the
+    /// whole clause is made of hash and bitmap lookups.
+    fn plan_index(&self, cond: &Expr, indexes: &Indexes) -> Option<(IndexPlan, bool)> {
+        match cond {
+            Expr::Conditional(left, token, right) => match token.t {
+                TokenType::And => match (
+                    self.plan_composite(cond, indexes),
+                    self.plan_index(left, indexes),
+                    self.plan_index(right, indexes),
+                ) {
+                    (Some(plan), _, _) => Some((plan, false)),
+                    (None, Some((l, l_exact)), Some((r, r_exact))) => Some((
+                        IndexPlan::Logical(Box::new(l), LogicalOp::And, Box::new(r)),
+                        l_exact && r_exact,
+                    )),
+                    (None, Some((plan, _)), None) | (None, None, Some((plan, _))) => {
+                        Some((plan, false))
+                    }
+                    (None, None, None) => None,
+                },
+                TokenType::Or => {
+                    let (left, l_exact) = self.plan_index(left, indexes)?;
+                    let (right, r_exact) = self.plan_index(right, indexes)?;
+                    Some((
+                        IndexPlan::Logical(Box::new(left), LogicalOp::Or, Box::new(right)),
+                        l_exact && r_exact,
+                    ))
+                }
+                _ => {
+                    let Expr::Literal(column) = left.as_ref() else {
+                        return None;
+                    };
+                    let column = *self.set.columns.get(&column.literal.to_string())?;
+                    let (encoded, expected) = self.literal(column, right);
+                    let find = |kind| {
+                        indexes.iter().position(|(columns, index)| {
+                            *columns == [column] && index.kind() == kind
+                        })
+                    };
+
+                    if token.t == TokenType::Equals
+                        && let Some(encoded) = &encoded
+                        && let Some(index) = find(IndexKind::Hash).or(find(IndexKind::Bitmap))
                     {
-                        continue;
+                        return Some((IndexPlan::Equals(index, encoded.clone()), true));
+                    }
+                    if let Some(index) = find(IndexKind::Bitmap) {
+                        let ty = self.set.types[column];
+                        let (_, predicate) = self.build_comparison(column, ty, &token.t, right);
+                        return Some((IndexPlan::Matching(index, predicate), true));
+                    }
+                    // B-tree keyed by tuples starting with the column works as well
+                    if let Some(index) = indexes.iter().position(|(columns, index)| {
+                        columns[0] == column && index.kind() == IndexKind::BTree
+                    }) {
+                        let ranges = key_ranges(&[], &token.t, &expected)?;
+                        return Some((IndexPlan::Range(index, ranges), false));
                     }
 
-                    let mut obj = vec![];
-                    for i in &selected_cols_ids {
-                        obj.push((*i, row[*i]));
+                    let zones = self.set.zones[column].as_ref()?;
+                    if zones.block_count() < 2 {
+                        return None;
                     }
+                    let accepts = zone_accepts(zones.ty, &token.t, encoded?)?;
+                    Some((IndexPlan::Blocks(column, accepts), false))
+                }
+            },
+            _ => None,
+        }
+    }
+
+    /// Looks up conjunction in B-tree over several columns. Leading columns of the index have
+    /// to be compared for equality, the next one may be compared by any operator. Used only
+    /// when more than one column gets constrained, the index covering most of them wins.
+    fn plan_composite(&self, cond: &Expr, indexes: &Indexes) -> Option<IndexPlan> {
+        let mut comparisons = vec![];
+        self.comparisons(cond, &mut comparisons);
+
+        let mut best: Option<(usize, IndexPlan)> = None;
+        for (i, (columns, index)) in indexes.iter().enumerate() {
+            if index.kind() != IndexKind::BTree {
+                continue;
+            }
+
+            let mut prefix = vec![];
+            let mut constrained = 0;
+            let mut ranges = None;
+            for column in columns {
+                let on_column = || comparisons.iter().filter(|(c, ..)| c == column);
+                if let Some((_, _, expected)) =
+                    on_column().find(|(_, op, _)| **op == TokenType::Equals)
+                {
+                    append_key(&mut prefix, expected);
+                    constrained += 1;
+                    continue;
+                }
+                ranges =
+                    on_column().find_map(|(_, op, expected)| key_ranges(&prefix, op, expected));
+                if ranges.is_some() {
+                    constrained += 1;
+                }
+                break;
+            }
+
+            if constrained > 1 && best.as_ref().is_none_or(|(most, _)| constrained > *most) {
+                let ranges = ranges.unwrap_or_else(|| {
+                    let end = tuple_key_end(&prefix);
+                    vec![(Bound::Included(prefix), Bound::Excluded(end))]
+                });
+                best = Some((constrained, IndexPlan::Range(i, ranges)));
+            }
+        }
+        best.map(|(_, plan)| plan)
+    }
+
+    /// Comparisons of columns joined by `and`, as column id, operator and expected value.
+    fn comparisons<'c>(&self, cond: &'c Expr, found: &mut Vec<(usize, &'c TokenType, Vec<u8>)>) {
+        let Expr::Conditional(left, token, right) = cond else {
+            return;
+        };
+        match token.t {
+            TokenType::And => {
+                self.comparisons(left, found);
+                self.comparisons(right, found);
+            }
+            TokenType::Or => {}
+            _ => {
+                if let Expr::Literal(column) = left.as_ref()
+                    && let Some(column) = self.set.columns.get(&column.literal.to_string())
+                {
+                    let (_, expected) = self.literal(*column, right);
+                    found.push((*column, &token.t, expected));
+                }
+            }
+        }
+    }
+
+    /// Rows yielded by plan, conjunctions and disjunctions are bitmap intersections and unions.
+    fn index_rows(&self, plan: &IndexPlan, indexes: &Indexes) -> Bitmap {
+        match plan {
+            IndexPlan::Range(index, ranges) => match &indexes[*index].1 {
+                ColumnIndex::BTree(tree) => lookup_rows(tree, ranges),
+                _ => unreachable!("Range lookup needs B-tree"),
+            },
+            IndexPlan::Equals(index, value) => match &indexes[*index].1 {
+                ColumnIndex::Hash(hash) => hash.get(value).iter().map(|row| *row as u32).collect(),
+                ColumnIndex::Bitmap(bitmap) => bitmap.get(value),
+                _ => unreachable!("Equality lookup needs hash or bitmap index"),
+            },
+            IndexPlan::Matching(index, predicate) => match &indexes[*index].1 {
+                ColumnIndex::Bitmap(bitmap) => bitmap.matching(predicate),
+                _ => unreachable!("Predicate lookup needs bitmap index"),
+            },
+            IndexPlan::Blocks(column, accepts) => {
+                let zones = self.set.zones[*column].as_ref().expect("Missing zone map");
+                let width = zones.ty.width().expect("Zones of text column");
+                zones
+                    .blocks(accepts)
+                    .flat_map(|block_id| fixed_rows(width, block_id))
+                    .take_while(|row| *row < self.set.rows.len())
+                    .map(|row| row as u32)
+                    .collect()
+            }
+            IndexPlan::Logical(left, op, right) => {
+                let left = self.index_rows(left, indexes);
+                let right = self.index_rows(right, indexes);
+                match op {
+                    LogicalOp::And => left.and(&right),
+                    LogicalOp::Or => left.or(&right),
+                }
+            }
+        }
+    }
+
+    /// Ids of rows that need to be checked against where clause, `None` means all of them.
+    /// Second value tells that the rows match where clause without checking.
+    fn candidate_rows(&self, conditions: Option<&Expr>) -> Option<(Bitmap, bool)> {
+        let indexes = self.set.indexes.read().unwrap();
+        let (plan, exact) = self.plan_index(conditions?, &indexes)?;
+        Some((self.index_rows(&plan, &indexes), exact))
+    }
+
+    /// Access path chosen for the statement.
+    pub(crate) fn access_path(&self, statement: &parser::Statement) -> AccessPath {
+        let parser::Statement::Get(_, _, Some(conditions), _) = statement else {
+            return AccessPath::FullScan;
+        };
+        let indexes = self.set.indexes.read().unwrap();
+        let Some((plan, _)) = self.plan_index(conditions, &indexes) else {
+            return AccessPath::FullScan;
+        };
+
+        let mut used = vec![];
+        self.used_indexes(&plan, &indexes, &mut used);
+        AccessPath::IndexScan(used)
+    }
+
+    /// Names of indexes used by plan, e.g. `age` for B-tree, `(first, last)` for B-tree over
+    /// two columns, `id (hash)` for hash index and `id (zone map)` when skipping blocks.
+    fn used_indexes(&self, plan: &IndexPlan, indexes: &Indexes, used: &mut Vec<String>) {
+        match plan {
+            IndexPlan::Range(index, _)
+            | IndexPlan::Equals(index, _)
+            | IndexPlan::Matching(index, _) => {
+                let (columns, index) = &indexes[*index];
+                let names: Vec<_> = columns.iter().map(|c| self.column_name(*c)).collect();
+                let mut name = match names.as_slice() {
+                    [name] => name.clone(),
+                    _ => format!("({})", names.join(", ")),
+                };
+                if index.kind() != IndexKind::BTree {
+                    name = format!("{} ({})", name, index.kind());
+                }
+                if !used.contains(&name) {
+                    used.push(name);
+                }
+            }
+            IndexPlan::Blocks(column, _) => {
+                let name = format!("{} (zone map)", self.column_name(*column));
+                if !used.contains(&name) {
+                    used.push(name);
+                }
+            }
+            IndexPlan::Logical(left, _, right) => {
+                self.used_indexes(left, indexes, used);
+                self.used_indexes(right, indexes, used);
+            }
+        }
+    }
+
+    fn column_name(&self, column: usize) -> String {
+        self.set
+            .columns
+            .iter()
+            .find(|(_, id)| **id == column)
+            .map(|(name, _)| name.clone())
+            .expect("Missing column")
+    }
+}
+
+impl<'a> Visitor<RowResult<'a>> for ColumnarExecutor<'a> {
+    fn visit(&self, expr: &parser::Statement) -> RowResult<'a> {
+        let set: &'a ColumnsWorkingSet = self.set;
+
+        match expr {
+            parser::Statement::Get(expr, _table, conditions, clauses) => {
+                let projections = self.build_projections(expr)?;
+                let candidates = self.candidate_rows(conditions.as_ref());
+                let filter = match candidates {
+                    Some((_, true)) => None,
+                    _ => conditions
+                        .as_ref()
+                        .map(|c| self.build_where_filter(c))
+                        .transpose()?,
+                };
+
+                // Rows found by indexes, kept in the same order as full scan would visit them
+                let rows: Box<dyn Iterator<Item = usize>> = match candidates {
+                    Some((ids, _)) => Box::new(
+                        ids.iter()
+                            .map(|id| id as usize)
+                            .collect::<Vec<_>>()
+                            .into_iter(),
+                    ),
+                    None => Box::new(0..set.rows.len()),
+                };
+
+                // Apply WHERE filters
+                let rows = rows.filter_map(|row| match &filter {
+                    Some(f) => self
+                        .evaluate_filter(f, row)
+                        .map(|m| m.then_some(row))
+                        .transpose(),
+                    None => Some(Ok(row)),
+                });
 
-                    res.push(obj);
+                if clauses.group_by.is_some()
+                    || clauses.having.is_some()
+                    || projections
+                        .iter()
+                        .any(|p| matches!(p, Projection::Aggregate(..)))
+                {
+                    return self.group(&projections, clauses, rows);
                 }
-                res
+
+                let rows = if clauses.order_by.is_empty() {
+                    // Rows are pulled lazily, so scan stops as soon as limit is reached
+                    rows.take(window(clauses).unwrap_or(usize::MAX))
+                        .collect::<ExecutionResult<Vec<usize>>>()?
+                } else {
+                    self.sort_rows(rows, &clauses.order_by, window(clauses))?
+                };
+                rows.into_iter()
+                    .skip(clauses.offset)
+                    .map(|row| self.project(&projections, row))
+                    .collect()
+            }
+            parser::Statement::Index(columns, kind, _) => {
+                set.create_index(&self.column_ids(columns)?, *kind)?;
+                Ok(vec![])
             }
         }
     }
 }
 
-fn get_column_names(expr: &parser::Expr) -> Vec<String> {
-    match expr {
-        parser::Expr::Literal(token) => vec![token.literal.to_string()],
-        parser::Expr::Multiple(left, right) => {
-            let mut names = get_column_names(left);
-            names.extend(get_column_names(right));
-            names
+impl<'a> CatalogExecutor<'a> {
+    fn executor(&self, statement: &parser::Statement) -> ExecutionResult<ColumnarExecutor<'a>> {
+        let table = match statement {
+            parser::Statement::Get(_, table, _, _) | parser::Statement::Index(_, _, table) => table,
+        };
+        let name = table.as_ref().map(parse_term);
+        Ok(ColumnarExecutor {
+            set: self.catalog.table(name.as_deref())?,
+        })
+    }
+
+    pub fn access_path(&self, statement: &parser::Statement) -> Result<AccessPath, ExecutionError> {
+        Ok(self.executor(statement)?.access_path(statement))
+    }
+}
+
+impl<'a> Visitor<RowResult<'a>> for CatalogExecutor<'a> {
+    fn visit(&self, expr: &parser::Statement) -> RowResult<'a> {
+        self.executor(expr)?.visit(expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::heap::Storage;
+    use crate::mem::{Dialect, LoadOptions, index_heap_columnar, load_catalog};
+    use crate::query::parser::CmdParser;
+    use crate::schema::ColumnTypes;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn load(lines: &[&str]) -> ColumnsWorkingSet {
+        load_into(lines, Storage::Memory)
+    }
+
+    fn load_into<S: AsRef<str>>(lines: &[S], storage: Storage) -> ColumnsWorkingSet {
+        let mut file = NamedTempFile::new().unwrap();
+        for line in lines {
+            writeln!(file, "{}", line.as_ref()).unwrap();
         }
-        _ => panic!("Invalid syntax"),
+        index_heap_columnar(
+            file.path().to_path_buf(),
+            &Dialect::default(),
+            None,
+            &ColumnTypes::new(),
+            storage,
+        )
+        .unwrap()
+    }
+
+    fn query(set: &ColumnsWorkingSet, q: &str) -> Vec<Vec<String>> {
+        let statement = CmdParser::new().parse_string(q).unwrap();
+        let executor = ColumnarExecutor { set };
+
+        statement
+            .accept(&executor)
+            .unwrap()
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|value| String::from_utf8(value.into_owned()).unwrap())
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn people() -> ColumnsWorkingSet {
+        load(&[
+            "name;age;city",
+            "Alice;25;NYC",
+            "Bob;9;LA",
+            "Charlie;100;Chicago",
+            "Dave;30;NYC",
+        ])
+    }
+
+    #[test]
+    fn test_equals() {
+        let set = people();
+        assert_eq!(
+            query(&set, r#"get name where city = "NYC""#),
+            [["Alice"], ["Dave"]]
+        );
+    }
+
+    #[test]
+    fn test_not_equals() {
+        let set = people();
+        assert_eq!(
+            query(&set, r#"get name where city != "NYC""#),
+            [["Bob"], ["Charlie"]]
+        );
+    }
+
+    #[test]
+    fn test_range_compares_numbers_numerically() {
+        let set = people();
+        assert_eq!(
+            query(&set, r#"get name where age > "25""#),
+            [["Charlie"], ["Dave"]]
+        );
+        assert_eq!(
+            query(&set, r#"get name where age <= "25""#),
+            [["Alice"], ["Bob"]]
+        );
+        assert_eq!(query(&set, r#"get name where age < "10""#), [["Bob"]]);
+        assert_eq!(
+            query(&set, r#"get name where age >= "30""#),
+            [["Charlie"], ["Dave"]]
+        );
+    }
+
+    #[test]
+    fn test_range_compares_text_lexicographically() {
+        let set = people();
+        assert_eq!(
+            query(&set, r#"get name where city < "M""#),
+            [["Bob"], ["Charlie"]]
+        );
+        assert_eq!(
+            query(&set, r#"get name where name >= "Charlie""#),
+            [["Charlie"], ["Dave"]]
+        );
+    }
+
+    #[test]
+    fn test_and_binds_tighter_than_or() {
+        let set = people();
+        // Left to right folding would give ((city = LA or city = NYC) and age > 26) -> Dave
+        assert_eq!(
+            query(
+                &set,
+                r#"get name where city = "LA" or city = "NYC" and age > "26""#
+            ),
+            [["Bob"], ["Dave"]]
+        );
+    }
+
+    #[test]
+    fn test_parentheses_group_conditions() {
+        let set = people();
+        assert_eq!(
+            query(
+                &set,
+                r#"get name where (city = "LA" or city = "NYC") and age > "26""#
+            ),
+            [["Dave"]]
+        );
+    }
+
+    #[test]
+    fn test_not_negates_subexpression() {
+        let set = people();
+        assert_eq!(
+            query(&set, r#"get name where not (city = "NYC" or age < "10")"#),
+            [["Charlie"]]
+        );
+        assert_eq!(
+            query(&set, r#"get name where !city = "NYC" and !name = "Bob""#),
+            [["Charlie"]]
+        );
+    }
+
+    #[test]
+    fn test_aggregates_over_all_rows() {
+        let set = people();
+        assert_eq!(
+            query(&set, "get count(*) sum(age) avg(age) min(age) max(age)"),
+            [["4", "164", "41", "9", "100"]]
+        );
+    }
+
+    #[test]
+    fn test_aggregates_over_filtered_rows() {
+        let set = people();
+        assert_eq!(
+            query(
+                &set,
+                r#"get count(name) min(name) max(name) where city = "NYC""#
+            ),
+            [["2", "Alice", "Dave"]]
+        );
+    }
+
+    #[test]
+    fn test_aggregates_without_matching_rows() {
+        let set = people();
+        assert_eq!(
+            query(
+                &set,
+                r#"get count(*) sum(age) avg(age) where city = "Paris""#
+            ),
+            [["0", "", ""]]
+        );
+    }
+
+    #[test]
+    fn test_plain_column_with_aggregate_is_error() {
+        let set = people();
+        assert_eq!(
+            query_error(&set, "get name count(*)"),
+            "Column must appear in group by clause or be used in aggregate"
+        );
+        assert_eq!(
+            query_error(&set, "get count(*) group by age having name = Bob"),
+            "Column must appear in group by clause or be used in aggregate"
+        );
+    }
+
+    #[test]
+    fn test_unknown_names_are_errors() {
+        let set = people();
+        assert_eq!(query_error(&set, "get salary"), "Missing column salary");
+        assert_eq!(
+            query_error(&set, "get name where salary > 5"),
+            "Missing column salary"
+        );
+        assert_eq!(
+            query_error(&set, "get name order by salary"),
+            "Missing column salary"
+        );
+        assert_eq!(
+            query_error(&set, "get max(salary)"),
+            "Missing column salary"
+        );
+    }
+
+    fn orders() -> ColumnsWorkingSet {
+        load(&[
+            "city;product;price",
+            "NYC;apple;3",
+            "LA;apple;4",
+            "NYC;pear;10",
+            "Chicago;plum;7",
+            "NYC;apple;5",
+            "LA;pear;1",
+        ])
+    }
+
+    #[test]
+    fn test_group_by_single_column() {
+        let set = orders();
+        assert_eq!(
+            query(&set, "get city count(*) sum(price) group by city"),
+            [["NYC", "3", "18"], ["LA", "2", "5"], ["Chicago", "1", "7"]]
+        );
+    }
+
+    #[test]
+    fn test_group_by_multiple_columns() {
+        let set = orders();
+        assert_eq!(
+            query(&set, "get city product count(*) group by city product"),
+            [
+                ["NYC", "apple", "2"],
+                ["LA", "apple", "1"],
+                ["NYC", "pear", "1"],
+                ["Chicago", "plum", "1"],
+                ["LA", "pear", "1"]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_group_by_with_where() {
+        let set = orders();
+        assert_eq!(
+            query(
+                &set,
+                r#"get product max(price) where price > "3" group by product"#
+            ),
+            [["apple", "5"], ["pear", "10"], ["plum", "7"]]
+        );
+    }
+
+    #[test]
+    fn test_having_filters_groups() {
+        let set = orders();
+        assert_eq!(
+            query(&set, r#"get city group by city having count(*) > "1""#),
+            [["NYC"], ["LA"]]
+        );
+    }
+
+    #[test]
+    fn test_having_on_group_column_and_aggregate() {
+        let set = orders();
+        assert_eq!(
+            query(
+                &set,
+                r#"get city avg(price) group by city having city != "NYC" and sum(price) >= "5""#
+            ),
+            [["LA", "2.5"], ["Chicago", "7"]]
+        );
+    }
+
+    #[test]
+    fn test_group_by_without_matching_rows() {
+        let set = orders();
+        assert!(
+            query(
+                &set,
+                r#"get city count(*) where price > "100" group by city"#
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_misplaced_aggregates_are_errors() {
+        let set = orders();
+        assert_eq!(
+            query_error(&set, r#"get city where count(*) > "1""#),
+            "Aggregates are not allowed in where clause"
+        );
+        assert_eq!(
+            query_error(&set, "get city order by max(price)"),
+            "Cannot order by aggregate without grouping"
+        );
+    }
+
+    #[test]
+    fn test_order_by_numeric_aware() {
+        let set = people();
+        assert_eq!(
+            query(&set, "get name age order by age"),
+            [
+                ["Bob", "9"],
+                ["Alice", "25"],
+                ["Dave", "30"],
+                ["Charlie", "100"]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_order_by_desc() {
+        let set = people();
+        assert_eq!(
+            query(&set, "get name order by age desc"),
+            [["Charlie"], ["Dave"], ["Alice"], ["Bob"]]
+        );
+    }
+
+    #[test]
+    fn test_order_by_multiple_keys() {
+        let set = orders();
+        assert_eq!(
+            query(&set, "get city price order by city asc price desc"),
+            [
+                ["Chicago", "7"],
+                ["LA", "4"],
+                ["LA", "1"],
+                ["NYC", "10"],
+                ["NYC", "5"],
+                ["NYC", "3"]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_order_by_column_not_selected_with_where() {
+        let set = people();
+        assert_eq!(
+            query(&set, r#"get name where city = "NYC" order by age desc"#),
+            [["Dave"], ["Alice"]]
+        );
+    }
+
+    #[test]
+    fn test_order_by_is_stable() {
+        let set = orders();
+        assert_eq!(
+            query(&set, "get product price order by product"),
+            [
+                ["apple", "3"],
+                ["apple", "4"],
+                ["apple", "5"],
+                ["pear", "10"],
+                ["pear", "1"],
+                ["plum", "7"]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_order_grouped_rows_by_aggregate() {
+        let set = orders();
+        assert_eq!(
+            query(&set, "get city group by city order by sum(price) desc"),
+            [["NYC"], ["Chicago"], ["LA"]]
+        );
+        assert_eq!(
+            query(
+                &set,
+                "get city count(*) group by city order by count(*) city"
+            ),
+            [["Chicago", "1"], ["LA", "2"], ["NYC", "3"]]
+        );
+    }
+
+    #[test]
+    fn test_limit() {
+        let set = people();
+        assert_eq!(query(&set, "get name limit 2"), [["Alice"], ["Bob"]]);
+        assert!(query(&set, "get name limit 0").is_empty());
+    }
+
+    #[test]
+    fn test_limit_with_offset() {
+        let set = people();
+        assert_eq!(
+            query(&set, "get name limit 2 offset 1"),
+            [["Bob"], ["Charlie"]]
+        );
+        assert_eq!(query(&set, "get name limit 10 offset 3"), [["Dave"]]);
+        assert!(query(&set, "get name limit 10 offset 4").is_empty());
+    }
+
+    #[test]
+    fn test_limit_with_where() {
+        let set = people();
+        assert_eq!(
+            query(&set, r#"get name where city = "NYC" limit 1 offset 1"#),
+            [["Dave"]]
+        );
+    }
+
+    #[test]
+    fn test_limit_with_order_by() {
+        let set = orders();
+        assert_eq!(
+            query(&set, "get product price order by price desc limit 2"),
+            [["pear", "10"], ["plum", "7"]]
+        );
+        assert_eq!(
+            query(&set, "get product price order by product limit 2 offset 2"),
+            [["apple", "5"], ["pear", "10"]]
+        );
+    }
+
+    #[test]
+    fn test_limit_larger_than_table() {
+        let set = orders();
+        let all = query(&set, "get product order by product");
+        assert_eq!(
+            query(&set, "get product order by product limit 100000000000"),
+            all
+        );
+        assert_eq!(
+            query(
+                &set,
+                &format!("get product order by product limit {} offset 1", usize::MAX)
+            ),
+            all[1..]
+        );
+    }
+
+    #[test]
+    fn test_limit_on_groups() {
+        let set = orders();
+        assert_eq!(
+            query(
+                &set,
+                "get city count(*) group by city order by count(*) desc limit 2"
+            ),
+            [["NYC", "3"], ["LA", "2"]]
+        );
+        assert_eq!(
+            query(&set, "get city group by city limit 1 offset 1"),
+            [["LA"]]
+        );
+    }
+
+    #[test]
+    fn test_bounded_sort_matches_full_sort() {
+        // Deterministic pseudo random values with plenty of duplicates
+        let values: Vec<(u32, usize)> = (0..500).map(|i| ((i * 7919) % 61, i as usize)).collect();
+        let cmp = |a: &(u32, usize), b: &(u32, usize)| a.0.cmp(&b.0);
+
+        let full = sort(values.iter().copied().map(Ok::<_, Infallible>), None, cmp).unwrap();
+        for n in [0, 1, 10, 61, 499, 500, 1000] {
+            let top = sort(
+                values.iter().copied().map(Ok::<_, Infallible>),
+                Some(n),
+                cmp,
+            )
+            .unwrap();
+            assert_eq!(top, full[..n.min(full.len())]);
+        }
+    }
+
+    #[test]
+    fn test_range_combined_with_and() {
+        let set = people();
+        assert_eq!(
+            query(&set, r#"get name where age > "20" and age < "50""#),
+            [["Alice"], ["Dave"]]
+        );
+    }
+
+    #[test]
+    fn test_table_selects_working_set() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("people.csv"),
+            "name;city\nAlice;NYC\nBob;LA\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("cities.csv"), "name;state\nNYC;NY\n").unwrap();
+        let catalog = load_catalog(dir.path().to_path_buf(), &LoadOptions::default()).unwrap();
+        let executor = CatalogExecutor { catalog: &catalog };
+
+        let run = |q: &str| -> Vec<Vec<u8>> {
+            CmdParser::new()
+                .parse_string(q)
+                .unwrap()
+                .accept(&executor)
+                .unwrap()
+                .into_iter()
+                .map(|row| row[0].to_vec())
+                .collect()
+        };
+
+        assert_eq!(run("get name @ people where city = LA"), [b"Bob"]);
+        assert_eq!(run("get name @ cities"), [b"NYC"]);
+        assert_eq!(run(r#"get state @ "cities" where name = NYC"#), [b"NY"]);
+    }
+
+    #[test]
+    fn test_long_values_span_blocks() {
+        let long = "x".repeat(20_000);
+        let set = load(&["id;body", &format!("1;{}", long), "2;short"]);
+
+        assert_eq!(query(&set, "get body where id = 1"), [[long.clone()]]);
+        assert_eq!(
+            query(&set, &format!("get id where body = {}", long)),
+            [["1"]]
+        );
+        assert_eq!(
+            query(&set, "get id max(body) group by id order by id limit 1"),
+            [["1".to_string(), long]]
+        );
+    }
+
+    fn query_error(set: &ColumnsWorkingSet, q: &str) -> String {
+        let statement = CmdParser::new().parse_string(q).unwrap();
+        let executor = ColumnarExecutor { set };
+        statement.accept(&executor).unwrap_err().0
+    }
+
+    fn access_path(set: &ColumnsWorkingSet, q: &str) -> String {
+        let statement = CmdParser::new().parse_string(q).unwrap();
+        ColumnarExecutor { set }.access_path(&statement).to_string()
+    }
+
+    fn mixed() -> ColumnsWorkingSet {
+        load(&[
+            "name;age;city",
+            "Alice;25;NYC",
+            "Bob;9;LA",
+            "Charlie;100;Chicago",
+            "Dave;30;NYC",
+            "Eve;unknown;LA",
+            "Frank;25.0;Boston",
+            "Grace;;NYC",
+        ])
+    }
+
+    fn typed() -> ColumnsWorkingSet {
+        load(&[
+            "name;age;price;active;joined",
+            "Alice;25;10;true;2024-01-31",
+            "Bob;9;9.75;false;2023-12-01",
+            "Charlie;100;100.5;true;2024-02-29",
+            "Dave;30;10;false;1999-12-31",
+        ])
+    }
+
+    #[test]
+    fn test_typed_columns_compare_by_type() {
+        let set = typed();
+        use ColumnType::*;
+        assert_eq!(set.types, [Text, Int, Float, Bool, Date]);
+
+        assert_eq!(
+            query(&set, "get name where age < 25"),
+            [["Bob"]],
+            "9 < 25 as numbers"
+        );
+        assert_eq!(query(&set, "get name where age = 025"), [["Alice"]]);
+        assert_eq!(
+            query(&set, "get name where price = 10.0"),
+            [["Alice"], ["Dave"]]
+        );
+        assert_eq!(
+            query(&set, "get name where active = TRUE"),
+            [["Alice"], ["Charlie"]]
+        );
+        assert_eq!(
+            query(&set, "get name where joined >= 2024-01-01"),
+            [["Alice"], ["Charlie"]]
+        );
+        // Literals of other type are compared as text
+        assert_eq!(
+            query(&set, "get name where age = 25.0"),
+            Vec::<Vec<String>>::new()
+        );
+        assert_eq!(query(&set, "get name where age > 24.5").len(), 3);
+        assert_eq!(query(&set, "get name where joined > 2024").len(), 2);
+    }
+
+    #[test]
+    fn test_long_numbers_compare_exactly() {
+        let set = load(&[
+            "id;score",
+            "12345678901234567891;1.50",
+            "12345678901234567892;1.5",
+        ]);
+        assert_eq!(
+            query(&set, "get id score where id = 12345678901234567891"),
+            [["12345678901234567891", "1.50"]]
+        );
+    }
+
+    #[test]
+    fn test_typed_columns_are_shown_as_text() {
+        let set = typed();
+        assert_eq!(
+            query(&set, "get price active joined where name = Bob"),
+            [["9.75", "false", "2023-12-01"]]
+        );
+        assert_eq!(
+            query(&set, "get price active where name = Dave"),
+            [["10", "false"]]
+        );
+    }
+
+    #[test]
+    fn test_typed_columns_sort_by_type() {
+        let set = typed();
+        assert_eq!(
+            query(&set, "get name order by joined desc"),
+            [["Charlie"], ["Alice"], ["Bob"], ["Dave"]]
+        );
+        assert_eq!(
+            query(&set, "get name order by price limit 2"),
+            [["Bob"], ["Alice"]]
+        );
+        assert_eq!(
+            query(&set, "get active count(*) group by active order by active"),
+            [["false", "2"], ["true", "2"]]
+        );
+    }
+
+    #[test]
+    fn test_typed_columns_aggregate_by_type() {
+        let set = typed();
+        assert_eq!(
+            query(&set, "get sum(age) avg(price) min(joined) max(joined)"),
+            [["164", "32.5625", "1999-12-31", "2024-02-29"]]
+        );
+        assert_eq!(
+            query(
+                &set,
+                "get active max(price) group by active having active = TRUE"
+            ),
+            [["true", "100.5"]]
+        );
+    }
+
+    #[test]
+    fn test_typed_index_matches_full_scan() {
+        let queries = [
+            "get name where age = 25",
+            "get name where age = 025",
+            "get name where age = 25.0",
+            "get name where age > 24.5",
+            "get name where price = 10",
+            "get name where price <= 10",
+            "get name where active = TRUE",
+            "get name where active != false",
+            "get name where joined >= 2024-01-01",
+            "get name where joined < 2024",
+            "get name where active = true and joined > 2024-02-01",
+        ];
+
+        let scanned = typed();
+        for kind in ["btree", "hash", "bitmap"] {
+            let indexed = typed();
+            for column in ["age", "price", "active", "joined"] {
+                query(&indexed, &format!("index {} using {}", column, kind));
+            }
+            for q in queries {
+                assert_eq!(query(&indexed, q), query(&scanned, q), "{} {}", kind, q);
+            }
+        }
+    }
+
+    /// Ages and cities with some of them missing.
+    fn sparse() -> ColumnsWorkingSet {
+        load(&[
+            "name;age;city",
+            "Alice;25;NYC",
+            "Bob;NA;LA",
+            "Carol;40;",
+            "Dave;;NYC",
+            "Eve;30;NULL",
+        ])
+    }
+
+    #[test]
+    fn test_is_null() {
+        let set = sparse();
+        assert_eq!(
+            query(&set, "get name where age is null"),
+            [["Bob"], ["Dave"]]
+        );
+        assert_eq!(
+            query(&set, "get name where age is not null"),
+            [["Alice"], ["Carol"], ["Eve"]]
+        );
+        assert_eq!(
+            query(&set, "get name where city is null and not age is null"),
+            [["Carol"], ["Eve"]]
+        );
+        assert!(query(&set, r#"get name where city = """#).is_empty());
+    }
+
+    #[test]
+    fn test_comparison_with_null_is_unknown() {
+        let set = sparse();
+        assert_eq!(
+            query(&set, "get name where age != 25"),
+            [["Carol"], ["Eve"]]
+        );
+        assert_eq!(query(&set, "get name where not age > 26"), [["Alice"]]);
+        assert_eq!(
+            query(&set, r#"get name where age > 26 or city = "NYC""#),
+            [["Alice"], ["Carol"], ["Dave"], ["Eve"]],
+            "true or unknown is true"
+        );
+        assert_eq!(
+            query(&set, r#"get name where not (age > 26 and city = "LA")"#),
+            [["Alice"], ["Dave"]],
+            "false and unknown is false, true and unknown stays unknown"
+        );
+    }
+
+    #[test]
+    fn test_aggregates_skip_nulls() {
+        let set = sparse();
+        assert_eq!(
+            query(
+                &set,
+                "get count(*) count(age) sum(age) min(age) max(age) min(city)"
+            ),
+            [["5", "3", "95", "25", "40", "LA"]]
+        );
+        assert_eq!(
+            query(&set, "get count(age) sum(age) avg(age) where age is null"),
+            [["0", "", ""]]
+        );
+    }
+
+    #[test]
+    fn test_nulls_are_grouped_together() {
+        let set = sparse();
+        assert_eq!(
+            query(&set, "get city count(*) group by city"),
+            [["NYC", "2"], ["LA", "1"], ["", "2"]]
+        );
+        assert_eq!(
+            query(&set, "get city count(*) group by city having city is null"),
+            [["", "2"]]
+        );
+        assert_eq!(
+            query(
+                &set,
+                "get city sum(age) group by city having sum(age) is null"
+            ),
+            [["LA", ""]]
+        );
+    }
+
+    #[test]
+    fn test_nulls_sort_last() {
+        let set = sparse();
+        assert_eq!(
+            query(&set, "get name order by age"),
+            [["Alice"], ["Eve"], ["Carol"], ["Bob"], ["Dave"]]
+        );
+        assert_eq!(
+            query(&set, "get name order by age desc"),
+            [["Bob"], ["Dave"], ["Carol"], ["Eve"], ["Alice"]]
+        );
+    }
+
+    /// Cities repeat often enough to be dictionary encoded.
+    fn cities() -> ColumnsWorkingSet {
+        let mut lines = vec!["name;city".to_string()];
+        for i in 0..24 {
+            lines.push(format!("n{};{}", i, ["NYC", "LA", "", "Chicago"][i % 4]));
+        }
+        load_into(&lines, Storage::Memory)
+    }
+
+    #[test]
+    fn test_dictionary_column_filters_by_code() {
+        let set = cities();
+        assert!(set.dictionaries[set.columns["city"]].is_some());
+
+        let count = |q: &str| query(&set, &format!("get count(*) where {}", q))[0][0].clone();
+        assert_eq!(count(r#"city = "NYC""#), "6");
+        assert_eq!(count(r#"city != "NYC""#), "12");
+        assert_eq!(count(r#"city = "Boston""#), "0");
+        assert_eq!(count(r#"city > "M""#), "6");
+        assert_eq!(count("city is null"), "6");
+        assert_eq!(count(r#"not city = "LA" and city < "M""#), "6");
+
+        assert_eq!(
+            query(&set, r#"get name city where city = "LA" limit 2"#),
+            [["n1", "LA"], ["n5", "LA"]]
+        );
+        assert_eq!(
+            query(&set, "get city count(*) group by city order by city"),
+            [["Chicago", "6"], ["LA", "6"], ["NYC", "6"], ["", "6"]]
+        );
+    }
+
+    #[test]
+    fn test_dictionary_index_matches_full_scan() {
+        let queries = [
+            r#"get name where city = "NYC""#,
+            r#"get name where city != "LA""#,
+            r#"get name where city <= "LA" or name = "n2""#,
+        ];
+
+        let scanned = cities();
+        for kind in ["btree", "hash", "bitmap"] {
+            let indexed = cities();
+            query(&indexed, &format!("index city using {}", kind));
+            for q in queries {
+                assert_eq!(query(&indexed, q), query(&scanned, q), "{} {}", kind, q);
+            }
+        }
+    }
+
+    #[test]
+    fn test_index_with_nulls_matches_full_scan() {
+        let queries = [
+            "get name where age = 25",
+            "get name where age != 25",
+            r#"get name where city != "NYC""#,
+            r#"get name where city = """#,
+            r#"get name where age > 0 or city = "LA""#,
+            r#"get name where city = "NYC" and age is null"#,
+            "get name where age is not null and age < 35",
+        ];
+
+        let scanned = sparse();
+        for kind in ["btree", "hash", "bitmap"] {
+            let indexed = sparse();
+            for column in ["age", "city"] {
+                query(&indexed, &format!("index {} using {}", column, kind));
+            }
+            for q in queries {
+                assert_eq!(query(&indexed, q), query(&scanned, q), "{} {}", kind, q);
+            }
+        }
+    }
+
+    #[test]
+    fn test_index_statement_creates_index() {
+        let set = people();
+        assert_eq!(access_path(&set, "get name where age = 25"), "full scan");
+
+        assert!(query(&set, "create index on age").is_empty());
+
+        assert_eq!(set.indexes.read().unwrap()[0].0, [1]);
+        assert_eq!(
+            access_path(&set, "get name where age = 25"),
+            "index scan on age"
+        );
+        assert_eq!(query(&set, "get name where age = 25"), [["Alice"]]);
+    }
+
+    #[test]
+    fn test_index_scan_matches_full_scan() {
+        let queries = [
+            "get name where age = 25",
+            "get name where age = 25.0",
+            "get name where age > 20",
+            "get name where age >= 25",
+            "get name where age < 30",
+            "get name where age <= 9",
+            "get name where age > abc",
+            "get name where age < abc",
+            r#"get name where age = """#,
+            "get name where city = NYC",
+            "get name where city > D",
+            "get name where city = NYC and age > 24",
+            "get name where city = LA or age >= 100",
+            "get name where city = NYC or not age = 25",
+            "get name where not city = NYC",
+            "get name where age != 25",
+            "get name where (age < 10 or age > 50) and city != LA",
+            "get name where age > 20 limit 2 offset 1",
+            "get city count(*) where age > 20 group by city",
+            "get name age where age >= 9 order by age desc",
+        ];
+
+        let scanned = mixed();
+        let indexed = mixed();
+        query(&indexed, "index age");
+        query(&indexed, "index city");
+
+        for q in queries {
+            assert_eq!(query(&indexed, q), query(&scanned, q), "{}", q);
+        }
+    }
+
+    #[test]
+    fn test_paged_storage_matches_memory() {
+        let mut lines = vec!["name;age;city".to_string()];
+        for i in 0..3000 {
+            let name = format!("person{}", i).repeat(1 + i % 7);
+            lines.push(format!("{};{};city{}", name, i % 90, i % 13));
+        }
+        lines.push(format!("{};1;far", "long".repeat(5000)));
+        let queries = [
+            "get name age",
+            "get name where age = 25",
+            "get name where city = city3 and age > 40",
+            "get city count(*) max(age) group by city",
+            "get name age where age >= 80 order by name desc limit 20",
+            "get count(*) where name > person2",
+        ];
+
+        let memory = load_into(&lines, Storage::Memory);
+        // Three cached blocks for each of the columns
+        let paged = load_into(&lines, Storage::Paged(9));
+        for q in queries {
+            assert_eq!(query(&paged, q), query(&memory, q), "{}", q);
+        }
+
+        query(&paged, "index age");
+        query(&paged, "index city using bitmap");
+        for q in queries {
+            assert_eq!(query(&paged, q), query(&memory, q), "{}", q);
+        }
+    }
+
+    #[test]
+    fn test_access_path() {
+        let set = mixed();
+        query(&set, "index age");
+        query(&set, "index city");
+
+        assert_eq!(access_path(&set, "get name"), "full scan");
+        assert_eq!(access_path(&set, "get name where name = Bob"), "full scan");
+        assert_eq!(access_path(&set, "get name where age != 25"), "full scan");
+        assert_eq!(
+            access_path(&set, "get name where not age = 25"),
+            "full scan"
+        );
+        assert_eq!(
+            access_path(&set, "get name where age < 30 and name = Bob"),
+            "index scan on age"
+        );
+        assert_eq!(
+            access_path(&set, "get name where age < 30 or name = Bob"),
+            "full scan"
+        );
+        assert_eq!(
+            access_path(&set, "get name where city = LA or age > 50 and age < 90"),
+            "index scan on city, age"
+        );
+    }
+
+    /// Ids spanning several heap blocks, every tenth score missing.
+    fn numbered() -> ColumnsWorkingSet {
+        let mut lines = vec!["id;score".to_string()];
+        for i in 0..5000 {
+            let score = if i % 10 == 0 {
+                String::new()
+            } else {
+                (i % 7).to_string()
+            };
+            lines.push(format!("{};{}", i, score));
+        }
+        load_into(&lines, Storage::Memory)
+    }
+
+    #[test]
+    fn test_zone_map_skips_blocks() {
+        let set = numbered();
+        let executor = ColumnarExecutor { set: &set };
+        let candidates = |q: &str| {
+            let parser::Statement::Get(_, _, conditions, _) =
+                CmdParser::new().parse_string(q).unwrap()
+            else {
+                unreachable!()
+            };
+            executor
+                .candidate_rows(conditions.as_ref())
+                .map(|(rows, _)| rows.len())
+        };
+
+        assert_eq!(
+            access_path(&set, "get id where id > 4990"),
+            "index scan on id (zone map)"
+        );
+        assert!(candidates("get id where id > 4990").unwrap() < 1100);
+        assert_eq!(candidates("get id where id < 0"), Some(0));
+        assert_eq!(
+            query(&set, "get id where id > 4997 or id = 7"),
+            [["7"], ["4998"], ["4999"]]
+        );
+        // Blocks hold values of every score, nothing to skip but still correct
+        assert_eq!(query(&set, "get count(*) where score >= 6"), [["642"]]);
+        // Text literal is compared as text, zones are not used
+        assert_eq!(access_path(&set, "get id where id > 4990.5"), "full scan");
+        assert_eq!(access_path(&set, "get id where not id > 4990"), "full scan");
+    }
+
+    #[test]
+    fn test_zone_map_matches_full_scan() {
+        let score = |id: usize| (!id.is_multiple_of(10)).then_some(id % 7);
+        let queries: [(&str, &dyn Fn(usize) -> bool); 6] = [
+            ("id = 1500", &|id| id == 1500),
+            ("id != 3", &|id| id != 3),
+            ("id <= 1022 and id >= 1020", &|id| {
+                (1020..=1022).contains(&id)
+            }),
+            ("score = 0", &|id| score(id) == Some(0)),
+            ("score < 1 and id > 4000", &|id| {
+                score(id) == Some(0) && id > 4000
+            }),
+            ("score is null and id < 50", &|id| {
+                score(id).is_none() && id < 50
+            }),
+        ];
+
+        let set = numbered();
+        for (q, matches) in queries {
+            let expected: Vec<_> = (0..5000)
+                .filter(|id| matches(*id))
+                .map(|id| vec![id.to_string()])
+                .collect();
+            assert_eq!(
+                query(&set, &format!("get id where {}", q)),
+                expected,
+                "{}",
+                q
+            );
+        }
+    }
+
+    #[test]
+    fn test_index_is_rebuilt() {
+        let set = people();
+        query(&set, "index age");
+        query(&set, "index age");
+        query(&set, "index age using hash");
+
+        let indexes = set.indexes.read().unwrap();
+        assert_eq!(indexes.len(), 2);
+        assert!(
+            matches!(&indexes[0], (cols, ColumnIndex::BTree(tree)) if *cols == [1] && tree.len() == 4)
+        );
+        assert!(matches!(&indexes[1], (cols, ColumnIndex::Hash(_)) if *cols == [1]));
+    }
+
+    #[test]
+    fn test_index_on_missing_column() {
+        let set = people();
+        assert_eq!(query_error(&set, "index salary"), "Missing column salary");
+        assert!(set.indexes.read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_hash_index_matches_full_scan() {
+        let queries = [
+            "get name where age = 25",
+            "get name where age = 25.0",
+            r#"get name where age = """#,
+            "get name where city = NYC",
+            "get name where city = Paris",
+            "get name where city = NYC and age = 25",
+            "get name where city = LA or age = 100",
+            "get name where city = NYC and age > 24",
+            "get name where city = NYC limit 1 offset 1",
+            "get city count(*) where city = NYC or city = LA group by city",
+        ];
+
+        let scanned = mixed();
+        let indexed = mixed();
+        query(&indexed, "index age using hash");
+        query(&indexed, "index city using hash");
+
+        for q in queries {
+            assert_eq!(query(&indexed, q), query(&scanned, q), "{}", q);
+        }
+    }
+
+    #[test]
+    fn test_hash_index_access_path() {
+        let set = mixed();
+        query(&set, "index city using hash");
+        query(&set, "index age");
+
+        assert_eq!(
+            access_path(&set, "get name where city = NYC"),
+            "index scan on city (hash)"
+        );
+        // Hash index answers equality only
+        assert_eq!(access_path(&set, "get name where city > NYC"), "full scan");
+        assert_eq!(
+            access_path(&set, "get name where city = NYC and age > 20"),
+            "index scan on city (hash), age"
+        );
+
+        // Hash index is preferred for equality when column has both
+        query(&set, "index age using hash");
+        assert_eq!(
+            access_path(&set, "get name where age = 25"),
+            "index scan on age (hash)"
+        );
+        assert_eq!(
+            access_path(&set, "get name where age >= 25"),
+            "index scan on age"
+        );
+    }
+
+    #[test]
+    fn test_bitmap_index_matches_full_scan() {
+        let queries = [
+            "get name where city = NYC",
+            "get name where city = Paris",
+            "get name where city != NYC",
+            "get name where city > D",
+            "get name where age >= 25",
+            "get name where age < abc",
+            "get name where age = 25.0",
+            "get name where city = NYC and age > 24",
+            "get name where city = LA or city = Boston",
+            "get name where (city = LA or city = NYC) and age != 25",
+            "get name where city = NYC or not age = 25",
+            "get name where city != LA limit 2 offset 1",
+            "get city count(*) where city = NYC or age > 20 group by city",
+        ];
+
+        let scanned = mixed();
+        let indexed = mixed();
+        query(&indexed, "index age using bitmap");
+        query(&indexed, "index city using bitmap");
+
+        for q in queries {
+            assert_eq!(query(&indexed, q), query(&scanned, q), "{}", q);
+        }
+    }
+
+    #[test]
+    fn test_bitmap_index_access_path() {
+        let set = mixed();
+        query(&set, "index city using bitmap");
+        query(&set, "index age");
+
+        assert_eq!(
+            access_path(&set, "get name where city != NYC"),
+            "index scan on city (bitmap)"
+        );
+        assert_eq!(
+            access_path(&set, "get name where city = NYC or city > M"),
+            "index scan on city (bitmap)"
+        );
+        assert_eq!(
+            access_path(&set, "get name where city = NYC and age > 20"),
+            "index scan on city (bitmap), age"
+        );
+        assert_eq!(
+            access_path(&set, "get name where not city = NYC"),
+            "full scan"
+        );
+    }
+
+    #[test]
+    fn test_composite_index_matches_full_scan() {
+        let queries = [
+            "get name where city = NYC and age = 25",
+            "get name where age = 25 and city = NYC",
+            "get name where city = NYC and age > 24",
+            "get name where city = NYC and age <= 25",
+            "get name where city = NYC and age < abc",
+            r#"get name where city = NYC and age = """#,
+            "get name where city = LA and age >= unknown",
+            "get name where city = NYC and age = 25 and name = Alice",
+            "get name where city = NYC and age != 25",
+            "get name where city = NYC",
+            "get name where city > Chicago and age = 25",
+            "get name where age = 25",
+            "get name where city = Boston or city = NYC and age < 50",
+            "get name where (city = NYC or city = LA) and age > 9",
+        ];
+
+        let scanned = mixed();
+        let indexed = mixed();
+        query(&indexed, "index city age");
+        query(&indexed, "create index on city age name");
+
+        for q in queries {
+            assert_eq!(query(&indexed, q), query(&scanned, q), "{}", q);
+        }
+    }
+
+    #[test]
+    fn test_composite_index_access_path() {
+        let set = mixed();
+        query(&set, "index city age");
+        query(&set, "index name");
+
+        assert_eq!(
+            access_path(&set, "get name where age = 25 and city = NYC"),
+            "index scan on (city, age)"
+        );
+        assert_eq!(
+            access_path(
+                &set,
+                "get name where city = NYC and age > 20 and name = Alice"
+            ),
+            "index scan on (city, age)"
+        );
+        // Prefix of single column is looked up as well
+        assert_eq!(
+            access_path(&set, "get name where city = NYC"),
+            "index scan on (city, age)"
+        );
+        assert_eq!(
+            access_path(&set, "get name where city = NYC and name = Alice"),
+            "index scan on (city, age), name"
+        );
+        // Range on leading column ends the prefix
+        assert_eq!(
+            access_path(&set, "get name where city > M and age = 25"),
+            "index scan on (city, age)"
+        );
+        assert_eq!(access_path(&set, "get name where age = 25"), "full scan");
+
+        // Index constraining more columns wins
+        query(&set, "index city age name");
+        assert_eq!(
+            access_path(
+                &set,
+                "get name where name = Alice and city = NYC and age = 25"
+            ),
+            "index scan on (city, age, name)"
+        );
+    }
+
+    #[test]
+    fn test_composite_hash_index_is_error() {
+        let err = mixed().create_index(&[0, 1], IndexKind::Hash).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "hash index can't span several columns");
+    }
+
+    #[test]
+    fn test_exact_plan() {
+        let set = mixed();
+        query(&set, "index city using hash");
+        query(&set, "index age");
+        let exact = |q: &str| {
+            let statement = CmdParser::new().parse_string(q).unwrap();
+            let parser::Statement::Get(_, _, conditions, _) = statement else {
+                unreachable!()
+            };
+            let executor = ColumnarExecutor { set: &set };
+            executor
+                .candidate_rows(conditions.as_ref())
+                .map(|(_, exact)| exact)
+        };
+
+        assert_eq!(exact("get name where city = NYC or city = LA"), Some(true));
+        assert_eq!(exact("get name where city = NYC and age = 25"), Some(false));
+        assert_eq!(
+            exact("get name where city = NYC and name = Dave"),
+            Some(false)
+        );
+        assert_eq!(exact("get name where name = Dave"), None);
+
+        query(&set, "index age using bitmap");
+        assert_eq!(exact("get name where city = NYC and age > 25"), Some(true));
+        assert_eq!(
+            exact("get name where (city = NYC or age != 9) and name = Dave"),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_queries_run_while_indexes_are_built() {
+        use std::thread;
+
+        let queries = [
+            "get name where city = NYC and age > 20",
+            "get name where age >= 25 or city = LA",
+            "get city count(*) group by city",
+        ];
+        let scanned = mixed();
+        let expected: Vec<_> = queries.iter().map(|q| query(&scanned, q)).collect();
+
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "name;age;city\nAlice;25;NYC\nBob;9;LA\nDave;30;NYC").unwrap();
+
+        let set = mixed();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for kind in ["btree", "hash", "bitmap"] {
+                    query(&set, &format!("index city using {}", kind));
+                    query(&set, &format!("index age using {}", kind));
+                }
+                query(&set, "index city age");
+            });
+
+            // Reload of another table does not stop readers
+            scope.spawn(|| {
+                for _ in 0..10 {
+                    let path = file.path().to_path_buf();
+                    let reloaded = index_heap_columnar(
+                        path,
+                        &Dialect::default(),
+                        None,
+                        &ColumnTypes::new(),
+                        Storage::Memory,
+                    )
+                    .unwrap();
+                    assert_eq!(
+                        query(&reloaded, "get name where city = NYC"),
+                        [["Alice"], ["Dave"]]
+                    );
+                }
+            });
+
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..20 {
+                        for (q, expected) in queries.iter().zip(&expected) {
+                            assert_eq!(&query(&set, q), expected, "{}", q);
+                        }
+                    }
+                });
+            }
+        });
+
+        assert_eq!(set.indexes.read().unwrap().len(), 7);
     }
 }
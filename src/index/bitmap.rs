@@ -0,0 +1,441 @@
+use std::collections::HashMap;
+
+/// Containers with at most this many values keep them in sorted array, denser ones switch to
+/// plain bitset. Both take 8KB at this point.
+const ARRAY_LIMIT: usize = 4096;
+const BITSET_WORDS: usize = (1 << 16) / 64;
+
+/// Compressed bitmap of row ids in roaring style. Ids are split by their high 16 bits into
+/// containers, each holding low 16 bits either as sorted array (sparse) or bitset (dense).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Bitmap {
+    /// Sorted by key, empty containers are never stored
+    containers: Vec<(u16, Container)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Container {
+    Array(Vec<u16>),
+    /// Bits with number of them set
+    Bitset(Box<[u64; BITSET_WORDS]>, usize),
+}
+
+fn split(value: u32) -> (u16, u16) {
+    ((value >> 16) as u16, value as u16)
+}
+
+impl Container {
+    fn len(&self) -> usize {
+        match self {
+            Container::Array(values) => values.len(),
+            Container::Bitset(_, len) => *len,
+        }
+    }
+
+    fn contains(&self, low: u16) -> bool {
+        match self {
+            Container::Array(values) => values.binary_search(&low).is_ok(),
+            Container::Bitset(bits, _) => bits[low as usize / 64] & (1 << (low % 64)) != 0,
+        }
+    }
+
+    fn insert(&mut self, low: u16) {
+        match self {
+            Container::Array(values) => {
+                if let Err(position) = values.binary_search(&low) {
+                    values.insert(position, low);
+                }
+            }
+            Container::Bitset(bits, len) => {
+                let word = &mut bits[low as usize / 64];
+                let bit = 1 << (low % 64);
+                if *word & bit == 0 {
+                    *word |= bit;
+                    *len += 1;
+                }
+            }
+        }
+        self.optimize();
+    }
+
+    fn bitset(values: &[u16]) -> Container {
+        let mut bits = Box::new([0u64; BITSET_WORDS]);
+        for &low in values {
+            bits[low as usize / 64] |= 1 << (low % 64);
+        }
+        Container::Bitset(bits, values.len())
+    }
+
+    /// Switches representation when container crosses `ARRAY_LIMIT`.
+    fn optimize(&mut self) {
+        match self {
+            Container::Array(values) if values.len() > ARRAY_LIMIT => {
+                *self = Container::bitset(values);
+            }
+            Container::Bitset(..) if self.len() <= ARRAY_LIMIT => {
+                *self = Container::Array(self.iter().collect());
+            }
+            _ => {}
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = u16> + '_> {
+        match self {
+            Container::Array(values) => Box::new(values.iter().copied()),
+            Container::Bitset(bits, _) => Box::new(
+                (0..=u16::MAX).filter(move |low| bits[*low as usize / 64] & (1 << (low % 64)) != 0),
+            ),
+        }
+    }
+
+    fn and(&self, other: &Container) -> Container {
+        let mut result = match (self, other) {
+            (Container::Array(a), Container::Array(b)) => {
+                let (mut i, mut j) = (0, 0);
+                let mut values = vec![];
+                while i < a.len() && j < b.len() {
+                    match a[i].cmp(&b[j]) {
+                        std::cmp::Ordering::Less => i += 1,
+                        std::cmp::Ordering::Greater => j += 1,
+                        std::cmp::Ordering::Equal => {
+                            values.push(a[i]);
+                            i += 1;
+                            j += 1;
+                        }
+                    }
+                }
+                Container::Array(values)
+            }
+            (Container::Array(values), bitset) | (bitset, Container::Array(values)) => {
+                Container::Array(
+                    values
+                        .iter()
+                        .copied()
+                        .filter(|low| bitset.contains(*low))
+                        .collect(),
+                )
+            }
+            (Container::Bitset(a, _), Container::Bitset(b, _)) => {
+                let mut bits = Box::new([0u64; BITSET_WORDS]);
+                let mut len = 0;
+                for (i, word) in bits.iter_mut().enumerate() {
+                    *word = a[i] & b[i];
+                    len += word.count_ones() as usize;
+                }
+                Container::Bitset(bits, len)
+            }
+        };
+        result.optimize();
+        result
+    }
+
+    fn or(&self, other: &Container) -> Container {
+        let mut result = match (self, other) {
+            (Container::Array(a), Container::Array(b)) => {
+                let (mut i, mut j) = (0, 0);
+                let mut values = Vec::with_capacity(a.len() + b.len());
+                while i < a.len() && j < b.len() {
+                    match a[i].cmp(&b[j]) {
+                        std::cmp::Ordering::Less => {
+                            values.push(a[i]);
+                            i += 1;
+                        }
+                        std::cmp::Ordering::Greater => {
+                            values.push(b[j]);
+                            j += 1;
+                        }
+                        std::cmp::Ordering::Equal => {
+                            values.push(a[i]);
+                            i += 1;
+                            j += 1;
+                        }
+                    }
+                }
+                values.extend_from_slice(&a[i..]);
+                values.extend_from_slice(&b[j..]);
+                Container::Array(values)
+            }
+            (Container::Array(values), Container::Bitset(bits, len))
+            | (Container::Bitset(bits, len), Container::Array(values)) => {
+                let mut bits = bits.clone();
+                let mut len = *len;
+                for &low in values {
+                    let word = &mut bits[low as usize / 64];
+                    let bit = 1 << (low % 64);
+                    if *word & bit == 0 {
+                        *word |= bit;
+                        len += 1;
+                    }
+                }
+                Container::Bitset(bits, len)
+            }
+            (Container::Bitset(a, _), Container::Bitset(b, _)) => {
+                let mut bits = Box::new([0u64; BITSET_WORDS]);
+                let mut len = 0;
+                for (i, word) in bits.iter_mut().enumerate() {
+                    *word = a[i] | b[i];
+                    len += word.count_ones() as usize;
+                }
+                Container::Bitset(bits, len)
+            }
+        };
+        result.optimize();
+        result
+    }
+}
+
+impl Bitmap {
+    pub(crate) fn new() -> Bitmap {
+        Bitmap::default()
+    }
+
+    pub(crate) fn insert(&mut self, value: u32) {
+        let (high, low) = split(value);
+        match self.containers.binary_search_by_key(&high, |(key, _)| *key) {
+            Ok(i) => self.containers[i].1.insert(low),
+            Err(i) => self
+                .containers
+                .insert(i, (high, Container::Array(vec![low]))),
+        }
+    }
+
+    pub(crate) fn contains(&self, value: u32) -> bool {
+        let (high, low) = split(value);
+        self.containers
+            .binary_search_by_key(&high, |(key, _)| *key)
+            .is_ok_and(|i| self.containers[i].1.contains(low))
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.containers.iter().map(|(_, c)| c.len()).sum()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.containers.is_empty()
+    }
+
+    /// Values in increasing order
+    pub(crate) fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.containers.iter().flat_map(|(high, container)| {
+            container
+                .iter()
+                .map(move |low| ((*high as u32) << 16) | low as u32)
+        })
+    }
+
+    /// Intersection
+    pub(crate) fn and(&self, other: &Bitmap) -> Bitmap {
+        let (mut i, mut j) = (0, 0);
+        let mut containers = vec![];
+        while i < self.containers.len() && j < other.containers.len() {
+            let (a_key, a) = &self.containers[i];
+            let (b_key, b) = &other.containers[j];
+            match a_key.cmp(b_key) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    let container = a.and(b);
+                    if container.len() > 0 {
+                        containers.push((*a_key, container));
+                    }
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        Bitmap { containers }
+    }
+
+    /// Union
+    pub(crate) fn or(&self, other: &Bitmap) -> Bitmap {
+        let (mut i, mut j) = (0, 0);
+        let mut containers = vec![];
+        while i < self.containers.len() && j < other.containers.len() {
+            let (a_key, a) = &self.containers[i];
+            let (b_key, b) = &other.containers[j];
+            match a_key.cmp(b_key) {
+                std::cmp::Ordering::Less => {
+                    containers.push((*a_key, a.clone()));
+                    i += 1;
+                }
+                std::cmp::Ordering::Greater => {
+                    containers.push((*b_key, b.clone()));
+                    j += 1;
+                }
+                std::cmp::Ordering::Equal => {
+                    containers.push((*a_key, a.or(b)));
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        containers.extend_from_slice(&self.containers[i..]);
+        containers.extend_from_slice(&other.containers[j..]);
+        Bitmap { containers }
+    }
+}
+
+impl FromIterator<u32> for Bitmap {
+    fn from_iter<I: IntoIterator<Item = u32>>(values: I) -> Bitmap {
+        let mut bitmap = Bitmap::new();
+        for value in values {
+            bitmap.insert(value);
+        }
+        bitmap
+    }
+}
+
+/// Bitmap index keeping a bitmap of rows for every distinct value of a column. Meant for
+/// columns with few distinct values, any comparison is answered by going through them.
+pub(crate) struct BitmapIndex {
+    values: HashMap<Vec<u8>, Bitmap>,
+}
+
+impl BitmapIndex {
+    pub(crate) fn new() -> BitmapIndex {
+        BitmapIndex {
+            values: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, value: &[u8], row: u32) {
+        match self.values.get_mut(value) {
+            Some(rows) => rows.insert(row),
+            None => {
+                self.values.insert(value.to_vec(), Bitmap::from_iter([row]));
+            }
+        }
+    }
+
+    /// Rows holding exactly `value`.
+    pub(crate) fn get(&self, value: &[u8]) -> Bitmap {
+        self.values.get(value).cloned().unwrap_or_default()
+    }
+
+    /// Rows whose value satisfies the predicate.
+    pub(crate) fn matching(&self, predicate: impl Fn(&[u8]) -> bool) -> Bitmap {
+        self.values
+            .iter()
+            .filter(|(value, _)| predicate(value))
+            .fold(Bitmap::new(), |rows, (_, bitmap)| rows.or(bitmap))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bitmap(values: impl IntoIterator<Item = u32>) -> Bitmap {
+        values.into_iter().collect()
+    }
+
+    fn is_bitset(bitmap: &Bitmap, high: u16) -> bool {
+        bitmap
+            .containers
+            .iter()
+            .any(|(key, c)| *key == high && matches!(c, Container::Bitset(..)))
+    }
+
+    #[test]
+    fn test_new_bitmap_is_empty() {
+        let bitmap = Bitmap::new();
+
+        assert!(bitmap.is_empty());
+        assert_eq!(bitmap.len(), 0);
+        assert!(!bitmap.contains(0));
+        assert_eq!(bitmap.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_insert_and_contains() {
+        let mut bitmap = Bitmap::new();
+        for value in [5, 1, 70_000, 5, u32::MAX] {
+            bitmap.insert(value);
+        }
+
+        assert_eq!(bitmap.len(), 4);
+        assert!(bitmap.contains(1));
+        assert!(bitmap.contains(70_000));
+        assert!(bitmap.contains(u32::MAX));
+        assert!(!bitmap.contains(2));
+        assert_eq!(bitmap.iter().collect::<Vec<_>>(), [1, 5, 70_000, u32::MAX]);
+    }
+
+    #[test]
+    fn test_dense_container_becomes_bitset() {
+        let mut bitmap = bitmap(0..ARRAY_LIMIT as u32);
+        assert!(!is_bitset(&bitmap, 0));
+
+        bitmap.insert(ARRAY_LIMIT as u32);
+        assert!(is_bitset(&bitmap, 0));
+        assert_eq!(bitmap.len(), ARRAY_LIMIT + 1);
+        assert_eq!(
+            bitmap.iter().collect::<Vec<_>>(),
+            (0..=ARRAY_LIMIT as u32).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_and() {
+        let a = bitmap([1, 3, 5, 70_000, 200_000]);
+        let b = bitmap([2, 3, 5, 7, 200_000]);
+
+        assert_eq!(a.and(&b).iter().collect::<Vec<_>>(), [3, 5, 200_000]);
+        assert!(a.and(&Bitmap::new()).is_empty());
+        // Containers left empty are dropped
+        assert!(bitmap([1]).and(&bitmap([2])).containers.is_empty());
+    }
+
+    #[test]
+    fn test_or() {
+        let a = bitmap([1, 3, 5, 70_000]);
+        let b = bitmap([2, 3, 200_000]);
+
+        assert_eq!(
+            a.or(&b).iter().collect::<Vec<_>>(),
+            [1, 2, 3, 5, 70_000, 200_000]
+        );
+        assert_eq!(Bitmap::new().or(&b), b);
+    }
+
+    #[test]
+    fn test_ops_across_container_kinds() {
+        let even = bitmap((0..20_000).step_by(2));
+        let odd = bitmap((1..20_000).step_by(2));
+        let sparse = bitmap([0, 1, 2, 3, 10_001]);
+        assert!(is_bitset(&even, 0));
+
+        let all = even.or(&odd);
+        assert_eq!(all.len(), 20_000);
+        assert!(is_bitset(&all, 0));
+
+        // Bitset intersection falls back to array once it gets sparse
+        let none = even.and(&odd);
+        assert!(none.is_empty());
+
+        assert_eq!(even.and(&sparse).iter().collect::<Vec<_>>(), [0, 2]);
+        assert_eq!(sparse.and(&odd).iter().collect::<Vec<_>>(), [1, 3, 10_001]);
+        assert_eq!(sparse.or(&even).len(), 10_000 + 3);
+        assert!(!is_bitset(&even.and(&bitmap(0..100)), 0));
+    }
+
+    #[test]
+    fn test_bitmap_index() {
+        let mut index = BitmapIndex::new();
+        for (row, value) in ["NYC", "LA", "NYC", "Boston", "LA"].iter().enumerate() {
+            index.insert(value.as_bytes(), row as u32);
+        }
+
+        assert_eq!(index.get(b"NYC").iter().collect::<Vec<_>>(), [0, 2]);
+        assert!(index.get(b"Paris").is_empty());
+        assert_eq!(
+            index
+                .matching(|value| value != b"NYC")
+                .iter()
+                .collect::<Vec<_>>(),
+            [1, 3, 4]
+        );
+        assert!(index.matching(|_| false).is_empty());
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+/// Hash index mapping exact value bytes to ids of rows holding them. Answers equality
+/// lookups only, but without walking a tree or rechecking values.
+pub(crate) struct HashIndex {
+    rows: HashMap<Vec<u8>, Vec<usize>>,
+}
+
+impl HashIndex {
+    pub(crate) fn new() -> HashIndex {
+        HashIndex {
+            rows: HashMap::new(),
+        }
+    }
+
+    /// Adds row holding `value`. Rows are expected in increasing order, so lookups return
+    /// them sorted.
+    pub(crate) fn insert(&mut self, value: &[u8], row: usize) {
+        match self.rows.get_mut(value) {
+            Some(rows) => rows.push(row),
+            None => {
+                self.rows.insert(value.to_vec(), vec![row]);
+            }
+        }
+    }
+
+    /// Rows holding exactly `value`.
+    pub(crate) fn get(&self, value: &[u8]) -> &[usize] {
+        self.rows.get(value).map_or(&[], Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_index_is_empty() {
+        let index = HashIndex::new();
+
+        assert!(index.rows.is_empty());
+        assert!(index.get(b"anything").is_empty());
+    }
+
+    #[test]
+    fn test_insert_and_get() {
+        let mut index = HashIndex::new();
+        index.insert(b"a", 0);
+        index.insert(b"b", 1);
+        index.insert(b"a", 2);
+
+        assert_eq!(index.get(b"a"), [0, 2]);
+        assert_eq!(index.get(b"b"), [1]);
+        assert!(index.get(b"c").is_empty());
+        assert_eq!(index.rows.len(), 2);
+    }
+
+    #[test]
+    fn test_values_match_exactly() {
+        let mut index = HashIndex::new();
+        index.insert(b"10", 0);
+        index.insert(b"10.0", 1);
+        index.insert(b"", 2);
+
+        // Unlike B-tree keys, numbers are not normalized
+        assert_eq!(index.get(b"10"), [0]);
+        assert_eq!(index.get(b"10.0"), [1]);
+        assert_eq!(index.get(b""), [2]);
+        assert!(index.get(b" 10").is_empty());
+    }
+}
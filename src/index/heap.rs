@@ -1,6 +1,16 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    sync::Mutex,
+};
+
 /// Heap that stores all the data in insert order fashion
 ///
-///
+/// Values that do not fit into a single block are moved out of line into chain of overflow
+/// blocks (similar to TOAST in Postgres). Block holding the row then keeps only a small
+/// pointer to the first overflow block.
 ///
 const MAX_BLOCK_SIZE: usize = 8192;
 const MAX_DATA_SIZE: usize = MAX_BLOCK_SIZE - size_of::<usize>() - size_of::<u16>();
@@ -9,11 +19,22 @@ const MAX_DATA_SIZE: usize = MAX_BLOCK_SIZE - size_of::<usize>() - size_of::<u16
 // There are MAX_DATA_SIZE possible offsets. Which we need 13 bits to encode (2^13 = 8192)
 // We also need to encode length of each entry. Length can be 1, also 13 bits.
 // We need 26 bits, so u32. So we will use 16 bits for each.
+// Highest bit of length marks value stored in overflow blocks.
 const OFFSET_MASK: u32 = (1 << 16) - 1;
-const LENGTH_MASK: u32 = !OFFSET_MASK;
+const OVERFLOW_FLAG: u32 = 1 << 31;
+const LENGTH_MASK: u32 = !OFFSET_MASK & !OVERFLOW_FLAG;
+
+// Overflow block starts with id of the next block in chain, the rest is value chunk.
+const LINK_SIZE: usize = size_of::<u64>();
+const OVERFLOW_CHUNK_SIZE: usize = MAX_DATA_SIZE - LINK_SIZE;
+const END_OF_CHAIN: u64 = u64::MAX;
+
+// Pointer stored in place of overflowed value: total length and first overflow block.
+const POINTER_SIZE: usize = 2 * size_of::<u64>();
 
 // [0..15] - offset
-// [16..31] - length
+// [16..30] - length
+// [31] - overflow flag
 #[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
 pub struct BlockOffset(u32);
 
@@ -26,6 +47,10 @@ impl BlockOffset {
         Self(value)
     }
 
+    fn overflow(start: u16) -> Self {
+        Self(Self::new(start, POINTER_SIZE as u16).0 | OVERFLOW_FLAG)
+    }
+
     fn get_start(&self) -> usize {
         (self.0 & OFFSET_MASK) as usize
     }
@@ -33,11 +58,273 @@ impl BlockOffset {
     fn get_length(&self) -> usize {
         ((self.0 & LENGTH_MASK) >> 16) as usize
     }
+
+    /// Whether value lives in overflow blocks, offset then points at overflow pointer.
+    pub(crate) fn is_overflow(&self) -> bool {
+        self.0 & OVERFLOW_FLAG != 0
+    }
+
+    /// Encoded form, as stored in snapshots.
+    pub(crate) fn to_bits(self) -> u32 {
+        self.0
+    }
+
+    pub(crate) fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+}
+
+/// Where `index`-th value lands in heap holding only values of `width` bytes. Such values are
+/// packed one after another, so their positions don't need to be stored.
+pub(crate) fn fixed_position(width: usize, index: usize) -> (usize, BlockOffset) {
+    let per_block = MAX_DATA_SIZE / width;
+    let start = (index % per_block) * width;
+    (
+        index / per_block,
+        BlockOffset::new(start as u16, width as u16),
+    )
+}
+
+/// Indexes of values of `width` bytes packed into given block, see [`fixed_position`].
+pub(crate) fn fixed_rows(width: usize, block_id: usize) -> std::ops::Range<usize> {
+    let per_block = MAX_DATA_SIZE / width;
+    block_id * per_block..(block_id + 1) * per_block
+}
+
+/// Storage of fixed size blocks backing a heap. Implementations only keep blocks, placing
+/// values into them, overflow chains and reads are shared by all of them.
+pub(crate) trait BlockStore: Send + Sync {
+    /// Number of blocks
+    fn block_count(&self) -> usize;
+
+    /// Appends empty block and returns its id.
+    fn init_block(&mut self) -> usize;
+
+    /// Block new values are appended to, `None` until the first one is allocated.
+    fn tail(&self) -> Option<usize>;
+
+    /// Makes block the one new values are appended to.
+    fn set_tail(&mut self, block_id: usize);
+
+    /// Offset where free space of block starts.
+    fn free_offset(&self, block_id: usize) -> usize;
+
+    /// Copies data into block at `start` and moves start of free space to `free_offset`.
+    fn write(
+        &mut self,
+        block_id: usize,
+        start: usize,
+        data: &[u8],
+        free_offset: usize,
+    ) -> std::io::Result<()>;
+
+    /// Bytes `start..start + len` of block. Missing block or range past its end is
+    /// `InvalidData` error.
+    fn read_bytes(
+        &self,
+        block_id: usize,
+        start: usize,
+        len: usize,
+    ) -> std::io::Result<Cow<'_, [u8]>>;
+
+    /// Allocates data on heap, returns (block_id, offset)
+    fn allocate(&mut self, data: &[u8]) -> std::io::Result<(usize, BlockOffset)> {
+        if data.len() > MAX_DATA_SIZE {
+            return allocate_overflow(self, data);
+        }
+
+        let block_id = self.get_free_block(data.len());
+        let start = self.free_offset(block_id);
+        self.write(block_id, start, data, start + data.len())?;
+
+        // This cast is safe as block data has to always be equal or less than MAX_DATA_SIZE
+        Ok((block_id, BlockOffset::new(start as u16, data.len() as u16)))
+    }
+
+    /// Block with enough space to contain {data_size} number of bytes. Values are appended
+    /// to the tail block, a new one becomes the tail once it is full. Allocation thus takes
+    /// constant time and blocks fill up in order, as [`fixed_position`] expects. Space left
+    /// in earlier blocks is not reused, loaded data is never deleted.
+    fn get_free_block(&mut self, data_size: usize) -> usize {
+        assert!(data_size <= MAX_DATA_SIZE, "data exceeds block capacity");
+
+        if let Some(tail) = self.tail()
+            && self.free_offset(tail) + data_size <= MAX_DATA_SIZE
+        {
+            return tail;
+        }
+        let block_id = self.init_block();
+        self.set_tail(block_id);
+        block_id
+    }
+
+    /// Reads value. Values kept in overflow blocks are reassembled into owned buffer, use
+    /// [`chunks`] to go through them without copying.
+    fn read(&self, block_id: usize, offset: BlockOffset) -> std::io::Result<Cow<'_, [u8]>> {
+        let mut chunks = chunks(self, block_id, offset)?;
+        if !offset.is_overflow() {
+            return chunks.next().unwrap();
+        }
+
+        let length = chunks.remaining;
+        let mut value = Vec::with_capacity(length);
+        for chunk in chunks {
+            value.extend_from_slice(&chunk?);
+        }
+
+        if value.len() != length {
+            return Err(invalid_data(format!(
+                "Broken overflow chain of block {}",
+                block_id
+            )));
+        }
+        Ok(Cow::Owned(value))
+    }
+
+    /// Writes blocks as they are: count, then free offset, data and CRC32 of both for every
+    /// block.
+    fn write_to(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        writer.write_all(&(self.block_count() as u64).to_le_bytes())?;
+        for block_id in 0..self.block_count() {
+            let free_offset = (self.free_offset(block_id) as u16).to_le_bytes();
+            let data = self.read_bytes(block_id, 0, MAX_DATA_SIZE)?;
+            let mut hasher = crc32fast::Hasher::new();
+            hasher.update(&free_offset);
+            hasher.update(&data);
+
+            writer.write_all(&free_offset)?;
+            writer.write_all(&data)?;
+            writer.write_all(&hasher.finalize().to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Appends blocks written by [`BlockStore::write_to`], failing on any checksum mismatch.
+    /// The last block with free space becomes the tail, overflow blocks are always full.
+    fn read_blocks(&mut self, reader: &mut dyn Read) -> std::io::Result<()> {
+        let mut count = [0u8; 8];
+        reader.read_exact(&mut count)?;
+
+        let mut data = vec![0u8; MAX_DATA_SIZE];
+        for i in 0..u64::from_le_bytes(count) as usize {
+            let mut free_offset = [0u8; 2];
+            let mut checksum = [0u8; 4];
+            reader.read_exact(&mut free_offset)?;
+            reader.read_exact(&mut data)?;
+            reader.read_exact(&mut checksum)?;
+
+            let mut hasher = crc32fast::Hasher::new();
+            hasher.update(&free_offset);
+            hasher.update(&data);
+            let free_offset = u16::from_le_bytes(free_offset) as usize;
+            if hasher.finalize() != u32::from_le_bytes(checksum) || free_offset > MAX_DATA_SIZE {
+                return Err(invalid_data(format!("Block {} is corrupted", i)));
+            }
+
+            let block_id = self.init_block();
+            self.write(block_id, 0, &data, free_offset)?;
+            if free_offset < MAX_DATA_SIZE {
+                self.set_tail(block_id);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invalid_data(msg: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+/// Chunked reader of value. Value stored inline is returned as a single chunk.
+fn chunks<S: BlockStore + ?Sized>(
+    store: &S,
+    block_id: usize,
+    offset: BlockOffset,
+) -> std::io::Result<Chunks<'_, S>> {
+    let data = store.read_bytes(block_id, offset.get_start(), offset.get_length())?;
+    if !offset.is_overflow() {
+        return Ok(Chunks {
+            store,
+            remaining: data.len(),
+            inline: Some(data),
+            next: None,
+        });
+    }
+
+    let length = u64::from_le_bytes(data[..8].try_into().unwrap()) as usize;
+    let first = u64::from_le_bytes(data[8..].try_into().unwrap()) as usize;
+    Ok(Chunks {
+        store,
+        inline: None,
+        next: Some(first),
+        remaining: length,
+    })
+}
+
+/// Spreads data over chain of dedicated blocks and stores pointer to the chain. Chain blocks
+/// never become the tail, the pointer lands in the current one.
+fn allocate_overflow<S: BlockStore + ?Sized>(
+    store: &mut S,
+    data: &[u8],
+) -> std::io::Result<(usize, BlockOffset)> {
+    let first = store.block_count();
+    let chunks = data.chunks(OVERFLOW_CHUNK_SIZE);
+    let count = chunks.len();
+
+    for (i, chunk) in chunks.enumerate() {
+        let block_id = store.init_block();
+        let next = if i + 1 < count {
+            (block_id + 1) as u64
+        } else {
+            END_OF_CHAIN
+        };
+        // Block is marked full, so that no other data lands in it
+        store.write(block_id, 0, &next.to_le_bytes(), MAX_DATA_SIZE)?;
+        store.write(block_id, LINK_SIZE, chunk, MAX_DATA_SIZE)?;
+    }
+
+    let mut pointer = [0u8; POINTER_SIZE];
+    pointer[..8].copy_from_slice(&(data.len() as u64).to_le_bytes());
+    pointer[8..].copy_from_slice(&(first as u64).to_le_bytes());
+
+    let (block_id, offset) = store.allocate(&pointer)?;
+    Ok((block_id, BlockOffset::overflow(offset.get_start() as u16)))
+}
+
+/// Where blocks of heaps live.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Storage {
+    #[default]
+    Memory,
+    /// Temporary files, with at most given number of blocks cached in memory, shared evenly
+    /// by heaps of a working set
+    Paged(usize),
+}
+
+impl Storage {
+    /// Paged storage caching at most given number of megabytes.
+    pub fn with_memory_limit(megabytes: usize) -> Storage {
+        Storage::Paged((megabytes << 20) / MAX_BLOCK_SIZE)
+    }
+
+    /// Creates store for one of `heaps` heaps of a working set.
+    pub(crate) fn create(self, heaps: usize) -> std::io::Result<Box<dyn BlockStore>> {
+        Ok(match self {
+            Storage::Memory => Box::new(BufferPool::new()),
+            Storage::Paged(capacity) => {
+                Box::new(PagedStore::new((capacity / heaps.max(1)).max(1))?)
+            }
+        })
+    }
 }
 
 // TODO: Find some better name
+/// Blocks kept in memory. Blocks are only changed through `&mut BufferPool`, so pool is `Sync`:
+/// any number of threads can read it at once, writer needs it exclusively (e.g. write latch of
+/// `RwLock`).
 pub(crate) struct BufferPool {
     blocks: Vec<Block>,
+    tail: Option<usize>,
 }
 
 struct Block {
@@ -55,74 +342,276 @@ impl Block {
         }
     }
 
+    #[cfg(test)]
     fn can_allocate(&self, size: usize) -> bool {
         self.free_offset as usize + size <= self.data.len()
     }
+}
+
+impl BufferPool {
+    pub(crate) fn new() -> BufferPool {
+        BufferPool {
+            blocks: vec![],
+            tail: None,
+        }
+    }
+}
 
-    fn allocate(&mut self, data: &[u8]) -> BlockOffset {
-        let start = self.free_offset;
-        self.data[start as usize..start as usize + data.len()].copy_from_slice(data);
+impl BlockStore for BufferPool {
+    fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
 
-        // This cast is safe as self.data.len() has to always be equal or less than MAX_DATA_SIZE
-        self.free_offset += data.len() as u16;
+    fn init_block(&mut self) -> usize {
+        let block_id = self.blocks.len();
+        self.blocks.push(Block::init(block_id));
+        block_id
+    }
 
-        BlockOffset::new(start, data.len() as u16)
+    fn tail(&self) -> Option<usize> {
+        self.tail
     }
 
-    fn read(&self, offset: BlockOffset) -> Option<&[u8]> {
-        let start = offset.get_start();
-        let len = offset.get_length();
+    fn set_tail(&mut self, block_id: usize) {
+        self.tail = Some(block_id);
+    }
 
-        if (start + len) > self.data.len() {
-            return None;
-        }
+    fn free_offset(&self, block_id: usize) -> usize {
+        self.blocks[block_id].free_offset as usize
+    }
+
+    fn write(
+        &mut self,
+        block_id: usize,
+        start: usize,
+        data: &[u8],
+        free_offset: usize,
+    ) -> std::io::Result<()> {
+        let block = &mut self.blocks[block_id];
+        debug_assert_eq!(block.block_id, block_id);
+        block.data[start..start + data.len()].copy_from_slice(data);
+        block.free_offset = free_offset as u16;
+        Ok(())
+    }
 
-        let data = &self.data[start..start + len];
-        Some(data)
+    fn read_bytes(
+        &self,
+        block_id: usize,
+        start: usize,
+        len: usize,
+    ) -> std::io::Result<Cow<'_, [u8]>> {
+        self.blocks
+            .get(block_id)
+            .and_then(|block| block.data.get(start..start + len))
+            .map(Cow::Borrowed)
+            .ok_or_else(|| invalid_data(missing(block_id, start, len)))
     }
 }
 
-impl BufferPool {
-    pub(crate) fn new() -> BufferPool {
-        BufferPool { blocks: vec![] }
+/// Blocks kept in anonymous temporary file, so that heaps may grow past available memory.
+/// At most `capacity` blocks are cached in frames, which are reused with clock (second
+/// chance) policy. Changed frames are written back once evicted.
+pub(crate) struct PagedStore {
+    free_offsets: Vec<u16>,
+    tail: Option<usize>,
+    /// Reads need to load and evict pages too, hence the latch. It is held for the whole
+    /// read, including file I/O on cache miss, so reads of one store run one at a time.
+    /// Every column has a store of its own, queries reading other columns are not blocked.
+    cache: Mutex<PageCache>,
+}
+
+struct PageCache {
+    file: File,
+    /// Blocks stored in file so far, the ones past it were never evicted
+    file_blocks: usize,
+    frames: Vec<Frame>,
+    /// Frame holding given block
+    table: HashMap<usize, usize>,
+    capacity: usize,
+    /// Clock hand, next frame considered for eviction
+    hand: usize,
+}
+
+struct Frame {
+    block_id: usize,
+    data: Box<[u8; MAX_DATA_SIZE]>,
+    /// Set on every access, cleared when clock hand passes
+    referenced: bool,
+    dirty: bool,
+}
+
+impl PagedStore {
+    pub(crate) fn new(capacity: usize) -> std::io::Result<PagedStore> {
+        assert!(capacity > 0, "Page cache needs at least one frame");
+        Ok(PagedStore {
+            free_offsets: vec![],
+            tail: None,
+            cache: Mutex::new(PageCache {
+                file: tempfile::tempfile()?,
+                file_blocks: 0,
+                frames: vec![],
+                table: HashMap::new(),
+                capacity,
+                hand: 0,
+            }),
+        })
     }
+}
 
-    /// Allocates data on heap, returns (block_id, offset)
-    pub(crate) fn allocate(&mut self, data: &[u8]) -> (usize, BlockOffset) {
-        let block_id = self.get_free_block(data.len());
+impl PageCache {
+    /// Frame holding the block, loaded from file when it is not cached. On error the block
+    /// stays uncached, a frame freed for it is left unused until it is evicted again.
+    fn frame(&mut self, block_id: usize) -> std::io::Result<&mut Frame> {
+        if let Some(&i) = self.table.get(&block_id) {
+            let frame = &mut self.frames[i];
+            frame.referenced = true;
+            return Ok(frame);
+        }
 
-        let offset = self.blocks[block_id].allocate(data);
+        let i = if self.frames.len() < self.capacity {
+            self.frames.push(Frame {
+                block_id,
+                data: Box::new([0u8; MAX_DATA_SIZE]),
+                referenced: false,
+                dirty: false,
+            });
+            self.frames.len() - 1
+        } else {
+            self.evict()?
+        };
 
-        (block_id, offset)
+        if block_id < self.file_blocks {
+            self.file
+                .seek(SeekFrom::Start((block_id * MAX_DATA_SIZE) as u64))
+                .and_then(|_| self.file.read_exact(&mut *self.frames[i].data))?;
+        } else {
+            self.frames[i].data.fill(0);
+        }
+
+        self.table.insert(block_id, i);
+        let frame = &mut self.frames[i];
+        frame.block_id = block_id;
+        frame.referenced = true;
+        Ok(frame)
     }
 
-    /// Looks up for block that has enough space to contain {data_size} number of bytes.
-    /// If there is none, it allocates new one.
-    // TODO: Track last free block
-    fn get_free_block(&mut self, data_size: usize) -> usize {
-        assert!(data_size <= MAX_DATA_SIZE, "data exceeds block capacity");
+    /// Frees frame not referenced since the hand passed it last time. Dirty frame that can't
+    /// be written back stays cached.
+    fn evict(&mut self) -> std::io::Result<usize> {
+        while self.frames[self.hand].referenced {
+            self.frames[self.hand].referenced = false;
+            self.hand = (self.hand + 1) % self.frames.len();
+        }
+        let i = self.hand;
+        self.hand = (self.hand + 1) % self.frames.len();
 
-        for block in &self.blocks {
-            if block.can_allocate(data_size) {
-                return block.block_id;
-            }
+        let frame = &mut self.frames[i];
+        if frame.dirty {
+            self.file
+                .seek(SeekFrom::Start((frame.block_id * MAX_DATA_SIZE) as u64))
+                .and_then(|_| self.file.write_all(&*frame.data))?;
+            self.file_blocks = self.file_blocks.max(frame.block_id + 1);
+            frame.dirty = false;
         }
+        // Frame left unused by failed load holds no block
+        if self.table.get(&frame.block_id) == Some(&i) {
+            self.table.remove(&frame.block_id);
+        }
+        Ok(i)
+    }
+}
 
-        self.init_block()
+impl BlockStore for PagedStore {
+    fn block_count(&self) -> usize {
+        self.free_offsets.len()
     }
 
     fn init_block(&mut self) -> usize {
-        let block_id = self.blocks.len();
-        self.blocks.push(Block::init(block_id));
-        block_id
+        self.free_offsets.push(0);
+        self.free_offsets.len() - 1
+    }
+
+    fn tail(&self) -> Option<usize> {
+        self.tail
+    }
+
+    fn set_tail(&mut self, block_id: usize) {
+        self.tail = Some(block_id);
     }
 
-    pub(crate) fn read(&self, block_id: usize, offset: BlockOffset) -> Option<&[u8]> {
-        if block_id >= self.blocks.len() {
+    fn free_offset(&self, block_id: usize) -> usize {
+        self.free_offsets[block_id] as usize
+    }
+
+    fn write(
+        &mut self,
+        block_id: usize,
+        start: usize,
+        data: &[u8],
+        free_offset: usize,
+    ) -> std::io::Result<()> {
+        let frame = self.cache.get_mut().unwrap().frame(block_id)?;
+        frame.data[start..start + data.len()].copy_from_slice(data);
+        frame.dirty = true;
+        self.free_offsets[block_id] = free_offset as u16;
+        Ok(())
+    }
+
+    fn read_bytes(
+        &self,
+        block_id: usize,
+        start: usize,
+        len: usize,
+    ) -> std::io::Result<Cow<'_, [u8]>> {
+        if block_id >= self.free_offsets.len() || start + len > MAX_DATA_SIZE {
+            return Err(invalid_data(missing(block_id, start, len)));
+        }
+        let mut cache = self.cache.lock().unwrap();
+        let frame = cache.frame(block_id)?;
+        Ok(Cow::Owned(frame.data[start..start + len].to_vec()))
+    }
+}
+
+fn missing(block_id: usize, start: usize, len: usize) -> String {
+    format!(
+        "Bytes {}..{} of block {} are not stored",
+        start,
+        start + len,
+        block_id
+    )
+}
+
+/// Iterator over parts of a value, see [`chunks`].
+pub(crate) struct Chunks<'a, S: ?Sized> {
+    store: &'a S,
+    inline: Option<Cow<'a, [u8]>>,
+    next: Option<usize>,
+    remaining: usize,
+}
+
+impl<'a, S: BlockStore + ?Sized> Iterator for Chunks<'a, S> {
+    type Item = std::io::Result<Cow<'a, [u8]>>;
+
+    fn next(&mut self) -> Option<std::io::Result<Cow<'a, [u8]>>> {
+        if let Some(data) = self.inline.take() {
+            self.remaining = 0;
+            return Some(Ok(data));
+        }
+        if self.remaining == 0 {
             return None;
         }
 
-        self.blocks[block_id].read(offset)
+        let block_id = self.next?;
+        let link = match self.store.read_bytes(block_id, 0, LINK_SIZE) {
+            Ok(link) => link,
+            Err(err) => return Some(Err(err)),
+        };
+        let len = self.remaining.min(OVERFLOW_CHUNK_SIZE);
+        self.remaining -= len;
+        let next = u64::from_le_bytes((*link).try_into().unwrap());
+        self.next = (next != END_OF_CHAIN).then_some(next as usize);
+
+        Some(self.store.read_bytes(block_id, LINK_SIZE, len))
     }
 }
 
@@ -147,28 +636,34 @@ mod tests {
         let mut heap = BufferPool::new();
         let data = b"hello";
 
-        let (block_id, offset) = heap.allocate(data);
+        let (block_id, offset) = heap.allocate(data).unwrap();
 
         assert_eq!(block_id, 0);
         assert_eq!(heap.blocks.len(), 1);
 
         let res = heap.read(block_id, offset).unwrap();
-        assert_eq!(data.as_slice(), res);
+        assert_eq!(data.as_slice(), &*res);
     }
 
     #[test]
     fn test_allocate_multiple_items_same_block() {
         let mut heap = BufferPool::new();
 
-        let (block_id1, offset1) = heap.allocate(b"first");
-        let (block_id2, offset2) = heap.allocate(b"second");
+        let (block_id1, offset1) = heap.allocate(b"first").unwrap();
+        let (block_id2, offset2) = heap.allocate(b"second").unwrap();
 
         assert_eq!(block_id1, 0);
         assert_eq!(block_id2, 0); // Same block
         assert_eq!(heap.blocks.len(), 1);
 
-        assert_eq!(b"first".as_slice(), heap.read(block_id1, offset1).unwrap());
-        assert_eq!(b"second".as_slice(), heap.read(block_id2, offset2).unwrap());
+        assert_eq!(
+            b"first".as_slice(),
+            &*heap.read(block_id1, offset1).unwrap()
+        );
+        assert_eq!(
+            b"second".as_slice(),
+            &*heap.read(block_id2, offset2).unwrap()
+        );
     }
 
     #[test]
@@ -176,10 +671,10 @@ mod tests {
         let mut heap = BufferPool::new();
         let data = b"test data";
 
-        let (block_id, offset) = heap.allocate(data);
+        let (block_id, offset) = heap.allocate(data).unwrap();
 
         let stored = heap.read(block_id, offset).unwrap();
-        assert_eq!(stored, data);
+        assert_eq!(&*stored, data);
     }
 
     #[test]
@@ -188,11 +683,11 @@ mod tests {
 
         // Fill up first block with data close to MAX_DATA_SIZE
         let large_data = vec![0u8; MAX_DATA_SIZE - 10];
-        heap.allocate(&large_data);
+        heap.allocate(&large_data).unwrap();
 
         // This should go to a new block
         let data = vec![1u8; 20];
-        let (block_id, offset) = heap.allocate(&data);
+        let (block_id, _offset) = heap.allocate(&data).unwrap();
 
         assert_eq!(block_id, 1);
         assert_eq!(heap.blocks.len(), 2);
@@ -212,9 +707,10 @@ mod tests {
 
     #[test]
     fn test_block_can_allocate_after_partial_use() {
-        let mut block = Block::init(0);
-        block.allocate(b"hello");
+        let mut heap = BufferPool::new();
+        heap.allocate(b"hello").unwrap();
 
+        let block = &heap.blocks[0];
         assert!(block.can_allocate(MAX_DATA_SIZE - 5));
         assert!(!block.can_allocate(MAX_DATA_SIZE - 4));
     }
@@ -226,6 +722,33 @@ mod tests {
         assert_eq!(block.free_offset, 0);
     }
 
+    #[test]
+    fn test_values_are_appended_to_tail() {
+        let mut heap = BufferPool::new();
+        heap.allocate(&[1u8; 100]).unwrap();
+        heap.allocate(&[2u8; MAX_DATA_SIZE - 50]).unwrap();
+
+        // First block still has room, but only the tail is filled
+        let (block_id, offset) = heap.allocate(&[3u8; 10]).unwrap();
+        assert_eq!(block_id, 1);
+        assert_eq!(offset.get_start(), MAX_DATA_SIZE - 50);
+        assert_eq!(heap.free_offset(0), 100);
+    }
+
+    #[test]
+    fn test_overflow_chain_does_not_become_tail() {
+        let mut heap = BufferPool::new();
+        heap.allocate(b"before").unwrap();
+        let (block_id, offset) = heap.allocate(&large_value(3 * MAX_DATA_SIZE)).unwrap();
+        assert!(offset.is_overflow());
+        assert_eq!(block_id, 0);
+
+        let (block_id, offset) = heap.allocate(b"after").unwrap();
+        assert_eq!(block_id, 0);
+        assert_eq!(offset.get_start(), 6 + POINTER_SIZE);
+        assert_eq!(heap.tail(), Some(0));
+    }
+
     #[test]
     fn test_get_free_block_initializes_first_block() {
         let mut heap = BufferPool::new();
@@ -238,7 +761,7 @@ mod tests {
     #[test]
     fn test_get_free_block_reuses_existing_block() {
         let mut heap = BufferPool::new();
-        heap.allocate(b"small");
+        heap.allocate(b"small").unwrap();
 
         let block_id = heap.get_free_block(100);
         assert_eq!(block_id, 0); // Reuses first block
@@ -252,19 +775,20 @@ mod tests {
         // Force creation of multiple blocks
         for i in 0..3 {
             let large_data = vec![i; MAX_DATA_SIZE];
-            heap.allocate(&large_data);
+            heap.allocate(&large_data).unwrap();
         }
 
         assert_eq!(heap.blocks.len(), 3);
         assert_eq!(heap.blocks[0].block_id, 0);
         assert_eq!(heap.blocks[1].block_id, 1);
         assert_eq!(heap.blocks[2].block_id, 2);
+        assert_eq!(heap.tail(), Some(2));
     }
 
     #[test]
     fn test_allocate_empty_data() {
         let mut heap = BufferPool::new();
-        let (block_id, offset) = heap.allocate(b"");
+        let (block_id, _offset) = heap.allocate(b"").unwrap();
 
         assert_eq!(block_id, 0);
     }
@@ -272,9 +796,318 @@ mod tests {
     #[test]
     fn test_allocate_single_byte() {
         let mut heap = BufferPool::new();
-        let (block_id, offset) = heap.allocate(b"x");
+        let (block_id, _offset) = heap.allocate(b"x").unwrap();
 
         assert_eq!(block_id, 0);
         assert_eq!(heap.blocks[0].data[0], b'x');
     }
+
+    fn large_value(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_value_of_block_size_stays_inline() {
+        let mut heap = BufferPool::new();
+        let data = large_value(MAX_DATA_SIZE);
+
+        let (block_id, offset) = heap.allocate(&data).unwrap();
+
+        assert!(!offset.is_overflow());
+        assert_eq!(heap.blocks.len(), 1);
+        assert!(matches!(heap.read(block_id, offset), Ok(Cow::Borrowed(_))));
+    }
+
+    #[test]
+    fn test_allocate_overflow_value() {
+        let mut heap = BufferPool::new();
+        let data = large_value(MAX_DATA_SIZE + 1);
+
+        let (block_id, offset) = heap.allocate(&data).unwrap();
+
+        assert!(offset.is_overflow());
+        // Two chunk blocks and one holding the pointer
+        assert_eq!(heap.blocks.len(), 3);
+        assert_eq!(block_id, 2);
+
+        let stored = heap.read(block_id, offset).unwrap();
+        assert!(matches!(stored, Cow::Owned(_)));
+        assert_eq!(&*stored, data.as_slice());
+    }
+
+    #[test]
+    fn test_overflow_value_longer_than_length_bits() {
+        let mut heap = BufferPool::new();
+        // Does not fit into 16 bit length of BlockOffset
+        let data = large_value(200_000);
+
+        let (block_id, offset) = heap.allocate(&data).unwrap();
+
+        assert_eq!(&*heap.read(block_id, offset).unwrap(), data.as_slice());
+    }
+
+    #[test]
+    fn test_overflow_blocks_are_not_reused() {
+        let mut heap = BufferPool::new();
+        let (id1, off1) = heap.allocate(b"before").unwrap();
+        let (id2, off2) = heap.allocate(&large_value(3 * MAX_DATA_SIZE)).unwrap();
+        let (id3, off3) = heap.allocate(b"after").unwrap();
+
+        // Small values and the overflow pointer share the first block
+        assert_eq!((id1, id2, id3), (0, 0, 0));
+        for block in &heap.blocks[1..] {
+            assert!(!block.can_allocate(1));
+        }
+
+        assert_eq!(&*heap.read(id1, off1).unwrap(), b"before");
+        assert_eq!(heap.read(id2, off2).unwrap().len(), 3 * MAX_DATA_SIZE);
+        assert_eq!(&*heap.read(id3, off3).unwrap(), b"after");
+    }
+
+    #[test]
+    fn test_chunks_of_overflow_value() {
+        let mut heap = BufferPool::new();
+        let data = large_value(2 * OVERFLOW_CHUNK_SIZE + 100);
+        let (block_id, offset) = heap.allocate(&data).unwrap();
+
+        let chunks: Vec<Cow<[u8]>> = chunks(&heap, block_id, offset)
+            .unwrap()
+            .collect::<std::io::Result<_>>()
+            .unwrap();
+
+        let sizes: Vec<usize> = chunks.iter().map(|c| c.len()).collect();
+        assert_eq!(sizes, [OVERFLOW_CHUNK_SIZE, OVERFLOW_CHUNK_SIZE, 100]);
+        assert_eq!(chunks.concat(), data);
+    }
+
+    #[test]
+    fn test_chunks_of_inline_value() {
+        let mut heap = BufferPool::new();
+        let (block_id, offset) = heap.allocate(b"inline").unwrap();
+
+        let chunks: Vec<Cow<[u8]>> = chunks(&heap, block_id, offset)
+            .unwrap()
+            .collect::<std::io::Result<_>>()
+            .unwrap();
+
+        assert_eq!(chunks, [b"inline".as_slice()]);
+    }
+
+    #[test]
+    fn test_fixed_width_values_are_packed() {
+        for width in [1, 4, 8] {
+            let mut heap = BufferPool::new();
+            for i in 0..3000 {
+                let ptr = heap.allocate(&vec![i as u8; width]).unwrap();
+                assert_eq!(ptr, fixed_position(width, i), "{} {}", width, i);
+                assert!(fixed_rows(width, ptr.0).contains(&i));
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_missing_block() {
+        let heap = BufferPool::new();
+        assert!(heap.read(0, BlockOffset::new(0, 1)).is_err());
+    }
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_buffer_pool_is_send_and_sync() {
+        assert_send_sync::<BufferPool>();
+    }
+
+    #[test]
+    fn test_concurrent_readers_with_single_writer() {
+        use std::sync::{Mutex, RwLock};
+        use std::thread;
+
+        let value = |i: usize| match i % 50 {
+            0 => large_value(MAX_DATA_SIZE + i),
+            _ => format!("value{}", i).into_bytes(),
+        };
+        let heap = RwLock::new(BufferPool::new());
+        // Pointers are published only after value is fully written
+        let published = Mutex::new(vec![]);
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for i in 0..500 {
+                    let ptr = heap.write().unwrap().allocate(&value(i)).unwrap();
+                    published.lock().unwrap().push((i, ptr));
+                }
+            });
+
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..50 {
+                        let ptrs = published.lock().unwrap().clone();
+                        let heap = heap.read().unwrap();
+                        for (i, (block_id, offset)) in ptrs {
+                            assert_eq!(&*heap.read(block_id, offset).unwrap(), value(i));
+                        }
+                    }
+                });
+            }
+        });
+
+        let heap = heap.into_inner().unwrap();
+        for (i, (block_id, offset)) in published.into_inner().unwrap() {
+            assert_eq!(&*heap.read(block_id, offset).unwrap(), value(i));
+        }
+    }
+
+    #[test]
+    fn test_write_and_read_blocks() -> std::io::Result<()> {
+        let mut heap = BufferPool::new();
+        let large = large_value(MAX_DATA_SIZE + 10);
+        let ptrs = [
+            heap.allocate(b"first").unwrap(),
+            heap.allocate(&large).unwrap(),
+            heap.allocate(b"last").unwrap(),
+        ];
+
+        let mut bytes = vec![];
+        heap.write_to(&mut bytes)?;
+        let mut restored = BufferPool::new();
+        restored.read_blocks(&mut bytes.as_slice())?;
+
+        assert_eq!(restored.blocks.len(), heap.blocks.len());
+        let values: Vec<_> = ptrs
+            .iter()
+            .map(|(block_id, offset)| restored.read(*block_id, *offset).unwrap())
+            .collect();
+        assert_eq!(values, [b"first".as_slice(), &large, b"last"]);
+
+        // New values still go into free space of restored blocks
+        assert_eq!(restored.allocate(b"more").unwrap().0, ptrs[2].0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_corrupted_block() {
+        let mut heap = BufferPool::new();
+        heap.allocate(b"value").unwrap();
+        let mut bytes = vec![];
+        heap.write_to(&mut bytes).unwrap();
+
+        bytes[12] ^= 1;
+        let error = BufferPool::new()
+            .read_blocks(&mut bytes.as_slice())
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        // Truncated file
+        let error = BufferPool::new()
+            .read_blocks(&mut &bytes[..100])
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    fn cached_blocks(store: &PagedStore) -> Vec<usize> {
+        let cache = store.cache.lock().unwrap();
+        let mut blocks: Vec<usize> = cache.table.keys().copied().collect();
+        blocks.sort();
+        blocks
+    }
+
+    #[test]
+    fn test_paged_store_reads_evicted_blocks() {
+        let mut heap = PagedStore::new(2).unwrap();
+        let value = |i: usize| match i % 100 {
+            0 => large_value(2 * MAX_DATA_SIZE + i),
+            _ => format!("value{}", i).repeat(20).into_bytes(),
+        };
+        let ptrs: Vec<_> = (0..2000)
+            .map(|i| heap.allocate(&value(i)).unwrap())
+            .collect();
+
+        assert!(heap.block_count() > 10);
+        assert_eq!(heap.cache.lock().unwrap().frames.len(), 2);
+        for (i, (block_id, offset)) in ptrs.into_iter().enumerate() {
+            assert_eq!(&*heap.read(block_id, offset).unwrap(), value(i));
+        }
+    }
+
+    #[test]
+    fn test_paged_store_gives_evicted_frame_a_second_chance() {
+        let mut heap = PagedStore::new(2).unwrap();
+        for _ in 0..3 {
+            heap.init_block();
+        }
+        heap.write(0, 0, b"a", 1).unwrap();
+        heap.write(1, 0, b"b", 1).unwrap();
+
+        // Both frames are referenced, hand clears them and evicts the first one
+        heap.write(2, 0, b"c", 1).unwrap();
+        assert_eq!(cached_blocks(&heap), [1, 2]);
+
+        // Block 1 was not used since the hand cleared it, unlike just loaded block 2
+        heap.read_bytes(0, 0, 1).unwrap();
+        assert_eq!(cached_blocks(&heap), [0, 2]);
+
+        let values: Vec<_> = (0..3)
+            .map(|block_id| heap.read_bytes(block_id, 0, 1).unwrap().into_owned())
+            .collect();
+        assert_eq!(values, [b"a", b"b", b"c"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_paged_store_reports_page_file_errors() {
+        let mut heap = PagedStore::new(1).unwrap();
+        heap.init_block();
+        heap.init_block();
+        heap.write(0, 0, b"a", 1).unwrap();
+
+        // Dirty block can't be written back, so it stays cached
+        heap.cache.get_mut().unwrap().file = File::open("/dev/null").unwrap();
+        assert!(heap.write(1, 0, b"b", 1).is_err());
+        assert_eq!(&*heap.read_bytes(0, 0, 1).unwrap(), b"a");
+
+        // Evicted block can't be read back
+        heap.cache.get_mut().unwrap().file = File::options().write(true).open("/dev/null").unwrap();
+        heap.write(1, 0, b"b", 1).unwrap();
+        assert!(heap.read_bytes(0, 0, 1).is_err());
+    }
+
+    #[test]
+    fn test_paged_store_write_and_read_blocks() -> std::io::Result<()> {
+        let mut heap = BufferPool::new();
+        let large = large_value(3 * MAX_DATA_SIZE);
+        let ptrs = [
+            heap.allocate(b"first").unwrap(),
+            heap.allocate(&large).unwrap(),
+        ];
+        let mut bytes = vec![];
+        heap.write_to(&mut bytes)?;
+
+        let mut restored = PagedStore::new(1)?;
+        restored.read_blocks(&mut bytes.as_slice())?;
+        let mut written = vec![];
+        restored.write_to(&mut written)?;
+
+        assert_eq!(written, bytes);
+        assert_eq!(&*restored.read(ptrs[1].0, ptrs[1].1).unwrap(), large);
+        assert_eq!(restored.tail(), Some(0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_storage_shares_memory_limit_between_heaps() {
+        assert_eq!(Storage::with_memory_limit(1), Storage::Paged(128));
+
+        let mut store = Storage::Paged(128).create(64).unwrap();
+        for i in 0..10 {
+            store.allocate(&large_value(MAX_DATA_SIZE - i)).unwrap();
+        }
+        assert_eq!(store.block_count(), 10);
+        assert_eq!(&*store.read(9, BlockOffset::new(0, 3)).unwrap(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_paged_store_is_send_and_sync() {
+        assert_send_sync::<PagedStore>();
+    }
 }
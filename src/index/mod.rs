@@ -1,2 +1,59 @@
+pub(crate) mod bitmap;
+pub(crate) mod hash;
 pub mod heap;
-mod tree;
+pub(crate) mod tree;
+pub(crate) mod zone;
+
+use bitmap::BitmapIndex;
+use hash::HashIndex;
+use tree::BTree;
+
+/// Kind of index created with `index <column> using <kind>`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IndexKind {
+    BTree,
+    Hash,
+    Bitmap,
+}
+
+impl IndexKind {
+    pub(crate) fn from_name(name: &str) -> Option<IndexKind> {
+        match name.to_lowercase().as_str() {
+            "btree" => Some(IndexKind::BTree),
+            "hash" => Some(IndexKind::Hash),
+            "bitmap" => Some(IndexKind::Bitmap),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for IndexKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IndexKind::BTree => write!(f, "btree"),
+            IndexKind::Hash => write!(f, "hash"),
+            IndexKind::Bitmap => write!(f, "bitmap"),
+        }
+    }
+}
+
+/// Index built over values of one column, or of several columns for B-tree.
+pub(crate) enum ColumnIndex {
+    /// Ordered by `value::tuple_key` of values of its columns as text, answers equality and
+    /// range lookups
+    BTree(BTree),
+    /// Exact stored value bytes, answers equality lookups
+    Hash(HashIndex),
+    /// Rows of every distinct value, answers any comparison exactly
+    Bitmap(BitmapIndex),
+}
+
+impl ColumnIndex {
+    pub(crate) fn kind(&self) -> IndexKind {
+        match self {
+            ColumnIndex::BTree(_) => IndexKind::BTree,
+            ColumnIndex::Hash(_) => IndexKind::Hash,
+            ColumnIndex::Bitmap(_) => IndexKind::Bitmap,
+        }
+    }
+}
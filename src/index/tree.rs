@@ -1,3 +1,5 @@
+use std::ops::Bound;
+
 use crate::index::heap::BlockOffset;
 
 const MAX_NODE_SIZE: usize = 8096;
@@ -6,7 +8,8 @@ struct MemManager {
     blocks: Vec<Node>,
 }
 
-// TODO: Make it thread safe
+// Nodes are only changed through `&mut BTree`, so the tree is `Sync`: it can be scanned from
+// many threads at once while a writer needs it exclusively (e.g. write latch of `RwLock`).
 impl MemManager {
     fn new() -> MemManager {
         MemManager { blocks: Vec::new() }
@@ -14,15 +17,43 @@ impl MemManager {
 
     fn allocate_internal(&mut self) -> usize {
         let block_id = self.blocks.len();
-        self.blocks.push(Node::Internal(Internal::new(block_id)));
+        self.blocks.push(Node::Internal(Internal::new()));
         block_id
     }
 
     fn allocate_leaf(&mut self) -> usize {
         let block_id = self.blocks.len();
-        self.blocks.push(Node::Leaf(Leaf::new(block_id)));
+        self.blocks.push(Node::Leaf(Leaf::new()));
         block_id
     }
+
+    fn internal(&self, block_id: usize) -> &Internal {
+        match &self.blocks[block_id] {
+            Node::Internal(internal) => internal,
+            Node::Leaf(_) => panic!("Expected internal node"),
+        }
+    }
+
+    fn internal_mut(&mut self, block_id: usize) -> &mut Internal {
+        match &mut self.blocks[block_id] {
+            Node::Internal(internal) => internal,
+            Node::Leaf(_) => panic!("Expected internal node"),
+        }
+    }
+
+    fn leaf(&self, block_id: usize) -> &Leaf {
+        match &self.blocks[block_id] {
+            Node::Leaf(leaf) => leaf,
+            Node::Internal(_) => panic!("Expected leaf node"),
+        }
+    }
+
+    fn leaf_mut(&mut self, block_id: usize) -> &mut Leaf {
+        match &mut self.blocks[block_id] {
+            Node::Leaf(leaf) => leaf,
+            Node::Internal(_) => panic!("Expected leaf node"),
+        }
+    }
 }
 
 // May add internal if siblings pointers are beneficial
@@ -33,9 +64,11 @@ enum Node {
 
 /// Representation of internal node
 struct Internal {
-    block_id: usize,
     /// Pointers down the tree. This ought to be sorted.
+    /// Key of an entry is the lower bound of keys in its subtree, first key is not checked.
     entries: Vec<InternalEntry>,
+    /// Bytes taken by entries
+    size: usize,
 }
 
 struct InternalEntry {
@@ -47,8 +80,12 @@ struct InternalEntry {
 }
 
 struct Leaf {
-    leaf_id: usize,
+    /// Sorted by key, equal keys are kept in insert order
     entries: Vec<LeafEntry>,
+    /// Bytes taken by entries
+    size: usize,
+    /// Right sibling, used by range scans
+    next: Option<usize>,
 }
 
 struct LeafEntry {
@@ -57,25 +94,635 @@ struct LeafEntry {
 }
 
 /// Pointer to data page containing the actual data
-struct Tid {
-    block_id: usize,
-    offset: BlockOffset,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Tid {
+    pub(crate) block_id: usize,
+    pub(crate) offset: BlockOffset,
+    /// Row the value belongs to
+    pub(crate) row: usize,
+}
+
+impl InternalEntry {
+    fn size(&self) -> usize {
+        self.key.len() + size_of::<usize>()
+    }
+}
+
+impl LeafEntry {
+    fn size(&self) -> usize {
+        self.key.len() + size_of::<Tid>()
+    }
 }
 
 impl Internal {
-    fn new(block_id: usize) -> Internal {
+    fn new() -> Internal {
         Internal {
-            block_id,
             entries: vec![],
+            size: 0,
         }
     }
+
+    /// Child that `key` is inserted into. Equal keys go right, after existing ones.
+    fn child_for_insert(&self, key: &[u8]) -> usize {
+        self.entries
+            .partition_point(|e| e.key.as_slice() <= key)
+            .saturating_sub(1)
+    }
+
+    /// Leftmost child that may contain `key`. Split can leave equal keys on both sides of
+    /// separator, so search has to start left of it.
+    fn child_for_search(&self, key: &[u8]) -> usize {
+        self.entries
+            .partition_point(|e| e.key.as_slice() < key)
+            .saturating_sub(1)
+    }
+
+    fn insert(&mut self, index: usize, entry: InternalEntry) {
+        self.size += entry.size();
+        self.entries.insert(index, entry);
+    }
 }
 
 impl Leaf {
-    fn new(leaf_id: usize) -> Leaf {
+    fn new() -> Leaf {
         Leaf {
-            leaf_id,
             entries: vec![],
+            size: 0,
+            next: None,
+        }
+    }
+
+    fn insert(&mut self, entry: LeafEntry) {
+        let index = self
+            .entries
+            .partition_point(|e| e.key.as_slice() <= entry.key.as_slice());
+        self.size += entry.size();
+        self.entries.insert(index, entry);
+    }
+}
+
+/// Index where roughly half of `size` bytes lies on each side. Both halves are non empty.
+fn split_point<T>(entries: &[T], size: usize, entry_size: impl Fn(&T) -> usize) -> usize {
+    let mut left = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        left += entry_size(entry);
+        if left >= size / 2 {
+            return (i + 1).min(entries.len() - 1);
+        }
+    }
+    entries.len() - 1
+}
+
+/// B+tree keyed on column bytes. Keys are compared bytewise and may repeat, every entry points
+/// at value stored in heap. Leaves are linked to their right sibling for range scans.
+pub(crate) struct BTree {
+    mem: MemManager,
+    root: usize,
+    /// Node is split once its entries take more than this many bytes
+    node_size: usize,
+    len: usize,
+}
+
+impl BTree {
+    pub(crate) fn new() -> BTree {
+        BTree::with_node_size(MAX_NODE_SIZE)
+    }
+
+    fn with_node_size(node_size: usize) -> BTree {
+        let mut mem = MemManager::new();
+        let root = mem.allocate_leaf();
+        BTree {
+            mem,
+            root,
+            node_size,
+            len: 0,
+        }
+    }
+
+    #[cfg(test)]
+    /// Number of stored entries
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    #[cfg(test)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub(crate) fn insert(&mut self, key: &[u8], tid: Tid) {
+        let entry = LeafEntry {
+            key: key.to_vec(),
+            tid,
+        };
+
+        if let Some((separator, right)) = self.insert_into(self.root, entry) {
+            let root = self.mem.allocate_internal();
+            let node = self.mem.internal_mut(root);
+            node.insert(
+                0,
+                InternalEntry {
+                    key: vec![],
+                    block_ptr: self.root,
+                },
+            );
+            node.insert(
+                1,
+                InternalEntry {
+                    key: separator,
+                    block_ptr: right,
+                },
+            );
+            self.root = root;
+        }
+        self.len += 1;
+    }
+
+    /// Inserts entry into subtree. Returns separator key and id of new right sibling when
+    /// the node had to be split.
+    fn insert_into(&mut self, block_id: usize, entry: LeafEntry) -> Option<(Vec<u8>, usize)> {
+        if let Node::Leaf(leaf) = &mut self.mem.blocks[block_id] {
+            leaf.insert(entry);
+            if leaf.size <= self.node_size || leaf.entries.len() < 2 {
+                return None;
+            }
+            return Some(self.split_leaf(block_id));
+        }
+
+        let index = self.mem.internal(block_id).child_for_insert(&entry.key);
+        let child = self.mem.internal(block_id).entries[index].block_ptr;
+        let (separator, right) = self.insert_into(child, entry)?;
+
+        let node = self.mem.internal_mut(block_id);
+        node.insert(
+            index + 1,
+            InternalEntry {
+                key: separator,
+                block_ptr: right,
+            },
+        );
+        if node.size <= self.node_size || node.entries.len() < 3 {
+            return None;
+        }
+        Some(self.split_internal(block_id))
+    }
+
+    fn split_leaf(&mut self, block_id: usize) -> (Vec<u8>, usize) {
+        let right_id = self.mem.allocate_leaf();
+
+        let left = self.mem.leaf_mut(block_id);
+        let mid = split_point(&left.entries, left.size, LeafEntry::size);
+        let entries = left.entries.split_off(mid);
+        let moved: usize = entries.iter().map(LeafEntry::size).sum();
+        left.size -= moved;
+        let next = left.next.replace(right_id);
+
+        let right = self.mem.leaf_mut(right_id);
+        right.entries = entries;
+        right.size = moved;
+        right.next = next;
+
+        (right.entries[0].key.clone(), right_id)
+    }
+
+    fn split_internal(&mut self, block_id: usize) -> (Vec<u8>, usize) {
+        let right_id = self.mem.allocate_internal();
+
+        let left = self.mem.internal_mut(block_id);
+        let mid = split_point(&left.entries, left.size, InternalEntry::size);
+        let entries = left.entries.split_off(mid);
+        let moved: usize = entries.iter().map(InternalEntry::size).sum();
+        left.size -= moved;
+
+        let right = self.mem.internal_mut(right_id);
+        right.entries = entries;
+        right.size = moved;
+
+        (right.entries[0].key.clone(), right_id)
+    }
+
+    /// Leaf where scan for keys starting at `key` begins. `None` means leftmost leaf.
+    fn find_leaf(&self, key: Option<&[u8]>) -> usize {
+        let mut block_id = self.root;
+        while let Node::Internal(node) = &self.mem.blocks[block_id] {
+            let index = key.map_or(0, |key| node.child_for_search(key));
+            block_id = node.entries[index].block_ptr;
+        }
+        block_id
+    }
+
+    #[cfg(test)]
+    /// All tids stored under `key`, in insert order.
+    pub(crate) fn get(&self, key: &[u8]) -> impl Iterator<Item = Tid> + '_ {
+        self.range(Bound::Included(key), Bound::Included(key))
+            .map(|(_, tid)| tid)
+    }
+
+    /// Entries with keys between given bounds, ordered by key.
+    pub(crate) fn range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Range<'_> {
+        let key = match start {
+            Bound::Included(key) | Bound::Excluded(key) => Some(key),
+            Bound::Unbounded => None,
+        };
+        let leaf = self.find_leaf(key);
+        let position = match start {
+            Bound::Unbounded => 0,
+            Bound::Included(key) => self
+                .mem
+                .leaf(leaf)
+                .entries
+                .partition_point(|e| e.key.as_slice() < key),
+            Bound::Excluded(key) => self
+                .mem
+                .leaf(leaf)
+                .entries
+                .partition_point(|e| e.key.as_slice() <= key),
+        };
+
+        Range {
+            tree: self,
+            leaf: Some(leaf),
+            position,
+            start: start.map(|key| key.to_vec()),
+            end: end.map(|key| key.to_vec()),
+        }
+    }
+
+    #[cfg(test)]
+    /// All entries ordered by key.
+    pub(crate) fn iter(&self) -> Range<'_> {
+        self.range(Bound::Unbounded, Bound::Unbounded)
+    }
+}
+
+/// Iterator over leaf entries, see [`BTree::range`].
+pub(crate) struct Range<'a> {
+    tree: &'a BTree,
+    leaf: Option<usize>,
+    position: usize,
+    /// Checked until first entry passes it. Entries before start can still show up when
+    /// start key is the last one in its leaf or equal keys span several leaves.
+    start: Bound<Vec<u8>>,
+    end: Bound<Vec<u8>>,
+}
+
+impl<'a> Iterator for Range<'a> {
+    type Item = (&'a [u8], Tid);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let leaf = self.tree.mem.leaf(self.leaf?);
+            let Some(entry) = leaf.entries.get(self.position) else {
+                self.leaf = leaf.next;
+                self.position = 0;
+                continue;
+            };
+
+            let before_start = match &self.start {
+                Bound::Unbounded => false,
+                Bound::Included(start) => entry.key < *start,
+                Bound::Excluded(start) => entry.key <= *start,
+            };
+            self.position += 1;
+            if before_start {
+                continue;
+            }
+            self.start = Bound::Unbounded;
+
+            let in_range = match &self.end {
+                Bound::Unbounded => true,
+                Bound::Included(end) => entry.key <= *end,
+                Bound::Excluded(end) => entry.key < *end,
+            };
+            if !in_range {
+                self.leaf = None;
+                return None;
+            }
+
+            return Some((entry.key.as_slice(), entry.tid));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::heap::{BlockStore, BufferPool};
+    use std::ops::RangeBounds;
+
+    /// Tids are taken from real heap, so they can be compared with values they point at.
+    fn tid(heap: &mut BufferPool, value: &[u8]) -> Tid {
+        let (block_id, offset) = heap.allocate(value).unwrap();
+        Tid {
+            block_id,
+            offset,
+            row: 0,
+        }
+    }
+
+    fn key(i: usize) -> Vec<u8> {
+        format!("key{:05}", i).into_bytes()
+    }
+
+    /// Small nodes so that a few dozen keys build a tree several levels deep.
+    fn small_tree() -> BTree {
+        BTree::with_node_size(64)
+    }
+
+    fn depth(tree: &BTree) -> usize {
+        let mut depth = 1;
+        let mut block_id = tree.root;
+        while let Node::Internal(node) = &tree.mem.blocks[block_id] {
+            block_id = node.entries[0].block_ptr;
+            depth += 1;
+        }
+        depth
+    }
+
+    fn keys(range: Range) -> Vec<Vec<u8>> {
+        range.map(|(key, _)| key.to_vec()).collect()
+    }
+
+    #[test]
+    fn test_new_tree_is_empty() {
+        let tree = BTree::new();
+
+        assert!(tree.is_empty());
+        assert_eq!(tree.iter().count(), 0);
+        assert_eq!(tree.get(b"missing").count(), 0);
+    }
+
+    #[test]
+    fn test_insert_and_get() {
+        let mut heap = BufferPool::new();
+        let mut tree = BTree::new();
+        let tid = tid(&mut heap, b"alice");
+
+        tree.insert(b"alice", tid);
+
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree.get(b"alice").collect::<Vec<_>>(), [tid]);
+        assert_eq!(tree.get(b"bob").count(), 0);
+    }
+
+    #[test]
+    fn test_leaf_splits_when_full() {
+        let mut heap = BufferPool::new();
+        let mut tree = small_tree();
+
+        for i in 0..4 {
+            tree.insert(&key(i), tid(&mut heap, &key(i)));
         }
+
+        assert_eq!(depth(&tree), 2);
+        let root = tree.mem.internal(tree.root);
+        assert_eq!(root.entries.len(), 2);
+        assert!(tree.mem.leaf(root.entries[0].block_ptr).next == Some(root.entries[1].block_ptr));
+    }
+
+    #[test]
+    fn test_internal_splits_when_full() {
+        let mut heap = BufferPool::new();
+        let mut tree = small_tree();
+
+        for i in 0..200 {
+            tree.insert(&key(i), tid(&mut heap, &key(i)));
+        }
+
+        assert!(depth(&tree) >= 3);
+        for i in 0..200 {
+            let tids: Vec<Tid> = tree.get(&key(i)).collect();
+            assert_eq!(tids.len(), 1);
+            assert_eq!(
+                &*heap.read(tids[0].block_id, tids[0].offset).unwrap(),
+                key(i)
+            );
+        }
+    }
+
+    #[test]
+    fn test_iter_is_sorted_for_any_insert_order() {
+        let mut heap = BufferPool::new();
+        let mut tree = small_tree();
+
+        // Deterministic permutation of 0..500
+        for i in (0..500).map(|i| (i * 7919) % 500) {
+            tree.insert(&key(i), tid(&mut heap, &key(i)));
+        }
+
+        assert_eq!(tree.len(), 500);
+        assert_eq!(keys(tree.iter()), (0..500).map(key).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_duplicates_across_leaves() {
+        let mut heap = BufferPool::new();
+        let mut tree = small_tree();
+
+        let mut expected = vec![];
+        for i in 0..50 {
+            tree.insert(b"a", tid(&mut heap, b"a"));
+            let t = tid(&mut heap, b"dup");
+            tree.insert(b"dup", t);
+            expected.push(t);
+            tree.insert(b"z", tid(&mut heap, b"z"));
+            tree.insert(&key(i), tid(&mut heap, &key(i)));
+        }
+
+        // Duplicates span several leaves and come back in insert order
+        assert!(depth(&tree) >= 2);
+        assert_eq!(tree.get(b"dup").collect::<Vec<_>>(), expected);
+        assert_eq!(tree.get(b"a").count(), 50);
+        assert_eq!(tree.get(b"z").count(), 50);
+    }
+
+    #[test]
+    fn test_range_bounds() {
+        let mut heap = BufferPool::new();
+        let mut tree = small_tree();
+        for i in 0..100 {
+            tree.insert(&key(i), tid(&mut heap, &key(i)));
+        }
+
+        let (k10, k20) = (key(10), key(20));
+        let range = |start, end| keys(tree.range(start, end));
+
+        assert_eq!(
+            range(Bound::Included(&k10), Bound::Excluded(&k20)),
+            (10..20).map(key).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            range(Bound::Excluded(&k10), Bound::Included(&k20)),
+            (11..=20).map(key).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            range(Bound::Unbounded, Bound::Excluded(&k10)),
+            (0..10).map(key).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            range(Bound::Excluded(&k20), Bound::Unbounded),
+            (21..100).map(key).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_range_between_existing_keys() {
+        let mut heap = BufferPool::new();
+        let mut tree = small_tree();
+        for i in (0..100).step_by(10) {
+            tree.insert(&key(i), tid(&mut heap, &key(i)));
+        }
+
+        let (start, end) = (key(15), key(45));
+        assert_eq!(
+            keys(tree.range(Bound::Included(&start), Bound::Included(&end))),
+            [key(20), key(30), key(40)]
+        );
+    }
+
+    #[test]
+    fn test_empty_range() {
+        let mut heap = BufferPool::new();
+        let mut tree = small_tree();
+        for i in 0..20 {
+            tree.insert(&key(i), tid(&mut heap, &key(i)));
+        }
+
+        let (k5, k3) = (key(5), key(3));
+        assert_eq!(
+            tree.range(Bound::Included(&k5), Bound::Included(&k3))
+                .count(),
+            0
+        );
+        assert_eq!(
+            tree.range(Bound::Excluded(&k5), Bound::Excluded(&k5))
+                .count(),
+            0
+        );
+        let past = key(100);
+        assert_eq!(
+            tree.range(Bound::Included(&past), Bound::Unbounded).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_keys_compare_bytewise() {
+        let mut heap = BufferPool::new();
+        let mut tree = BTree::new();
+        for k in [b"b".as_slice(), b"", b"ab", b"a", b"\xff"] {
+            tree.insert(k, tid(&mut heap, k));
+        }
+
+        assert_eq!(
+            keys(tree.iter()),
+            [
+                b"".to_vec(),
+                b"a".to_vec(),
+                b"ab".to_vec(),
+                b"b".to_vec(),
+                b"\xff".to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_key_larger_than_node() {
+        let mut heap = BufferPool::new();
+        let mut tree = small_tree();
+        let large = vec![b'x'; 1000];
+
+        tree.insert(&large, tid(&mut heap, b"large"));
+        for i in 0..10 {
+            tree.insert(&key(i), tid(&mut heap, &key(i)));
+        }
+
+        assert_eq!(tree.get(&large).count(), 1);
+        assert_eq!(tree.iter().count(), 11);
+    }
+
+    #[test]
+    fn test_split_point_keeps_both_halves() {
+        let sizes = [100, 1, 1];
+        assert_eq!(split_point(&sizes, 102, |s| *s), 1);
+
+        let sizes = [1, 1, 100];
+        assert_eq!(split_point(&sizes, 102, |s| *s), 2);
+
+        let sizes = [10, 10, 10, 10];
+        assert_eq!(split_point(&sizes, 40, |s| *s), 2);
+    }
+
+    #[test]
+    fn test_range_matches_sorted_vec() {
+        let mut heap = BufferPool::new();
+        let mut tree = small_tree();
+        // Plenty of duplicates, so equal keys end up in several leaves
+        let mut values: Vec<Vec<u8>> = (0..400).map(|i| key((i * 7919) % 37)).collect();
+        for value in &values {
+            tree.insert(value, tid(&mut heap, value));
+        }
+        values.sort();
+
+        for a in 0..38 {
+            for b in a..38 {
+                let (ka, kb) = (key(a), key(b));
+                let bounds = [
+                    (
+                        Bound::Included(ka.as_slice()),
+                        Bound::Included(kb.as_slice()),
+                    ),
+                    (
+                        Bound::Excluded(ka.as_slice()),
+                        Bound::Excluded(kb.as_slice()),
+                    ),
+                ];
+                for (start, end) in bounds {
+                    let expected: Vec<Vec<u8>> = values
+                        .iter()
+                        .filter(|v| (start, end).contains(v.as_slice()))
+                        .cloned()
+                        .collect();
+                    assert_eq!(keys(tree.range(start, end)), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_concurrent_readers_with_single_writer() {
+        use std::sync::RwLock;
+        use std::thread;
+
+        let mut heap = BufferPool::new();
+        let tid = tid(&mut heap, b"value");
+        let tree = RwLock::new(small_tree());
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                // Reverse order splits nodes on the left edge where scans start
+                for i in (0..1000).rev() {
+                    tree.write().unwrap().insert(&key(i), tid);
+                }
+            });
+
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let mut seen = 0;
+                    while seen < 1000 {
+                        let tree = tree.read().unwrap();
+                        let keys = keys(tree.iter());
+                        assert!(keys.is_sorted());
+                        assert_eq!(keys.len(), tree.len());
+                        // Keys are only added
+                        assert!(keys.len() >= seen);
+                        seen = keys.len();
+                    }
+                });
+            }
+        });
+
+        let tree = tree.into_inner().unwrap();
+        assert_eq!(keys(tree.iter()), (0..1000).map(key).collect::<Vec<_>>());
     }
 }
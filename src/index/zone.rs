@@ -0,0 +1,99 @@
+use std::cmp::Ordering;
+
+use crate::schema::ColumnType;
+
+/// Smallest and largest value of every block of column heap, recorded as values are
+/// allocated. Lets scans skip blocks holding no value a range predicate could accept.
+pub(crate) struct ZoneMap {
+    pub(crate) ty: ColumnType,
+    /// Indexed by block id, `None` for blocks without values (e.g. holding only nulls)
+    pub(crate) zones: Vec<Option<(Vec<u8>, Vec<u8>)>>,
+}
+
+impl ZoneMap {
+    pub(crate) fn new(ty: ColumnType) -> ZoneMap {
+        ZoneMap { ty, zones: vec![] }
+    }
+
+    /// Widens zone of the block by value stored in it.
+    pub(crate) fn record(&mut self, block_id: usize, value: &[u8]) {
+        if self.zones.len() <= block_id {
+            self.zones.resize(block_id + 1, None);
+        }
+        match &mut self.zones[block_id] {
+            Some((min, max)) => {
+                if self.ty.compare(value, min) == Ordering::Less {
+                    *min = value.to_vec();
+                } else if self.ty.compare(value, max) == Ordering::Greater {
+                    *max = value.to_vec();
+                }
+            }
+            zone => *zone = Some((value.to_vec(), value.to_vec())),
+        }
+    }
+
+    /// Smallest and largest value stored in the block.
+    pub(crate) fn zone(&self, block_id: usize) -> Option<(&[u8], &[u8])> {
+        let (min, max) = self.zones.get(block_id)?.as_ref()?;
+        Some((min, max))
+    }
+
+    /// Number of blocks zones were recorded for, including the ones left empty.
+    pub(crate) fn block_count(&self) -> usize {
+        self.zones.len()
+    }
+
+    /// Blocks whose zone is accepted by `accepts(min, max)`. Blocks without values are
+    /// never accepted.
+    pub(crate) fn blocks<'z>(
+        &'z self,
+        accepts: impl Fn(&[u8], &[u8]) -> bool + 'z,
+    ) -> impl Iterator<Item = usize> + 'z {
+        (0..self.zones.len()).filter(move |block_id| {
+            self.zone(*block_id)
+                .is_some_and(|(min, max)| accepts(min, max))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(value: i64) -> Vec<u8> {
+        ColumnType::Int
+            .encode(value.to_string().as_bytes())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_record_keeps_min_and_max_by_type() {
+        let mut zones = ZoneMap::new(ColumnType::Int);
+        for value in [5, -3, 40, 7] {
+            zones.record(0, &int(value));
+        }
+        zones.record(2, &int(100));
+
+        assert_eq!(zones.zone(0), Some((&int(-3)[..], &int(40)[..])));
+        assert_eq!(zones.zone(1), None);
+        assert_eq!(zones.zone(2), Some((&int(100)[..], &int(100)[..])));
+        assert_eq!(zones.zone(3), None);
+        assert_eq!(zones.block_count(), 3);
+    }
+
+    #[test]
+    fn test_blocks_skip_zones_that_cannot_match() {
+        let mut zones = ZoneMap::new(ColumnType::Int);
+        for (block_id, values) in [[1, 10], [11, 20], [21, 30]].iter().enumerate() {
+            for value in values {
+                zones.record(block_id, &int(*value));
+            }
+        }
+        zones.zones.push(None);
+
+        let expected = int(15);
+        let greater = |_: &[u8], max: &[u8]| ColumnType::Int.compare(max, &expected).is_gt();
+        assert_eq!(zones.blocks(greater).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(zones.blocks(|_, _| true).collect::<Vec<_>>(), [0, 1, 2]);
+    }
+}
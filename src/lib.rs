@@ -0,0 +1,10 @@
+//! Loading CSV files into columnar working sets and running queries against them. Used by
+//! the REPL binary and benchmarks.
+mod aggregate;
+pub mod executor;
+pub mod index;
+pub mod mem;
+pub mod query;
+pub mod schema;
+mod snapshot;
+mod value;
@@ -1,17 +1,14 @@
-use crate::executor::ColumnarExecutor;
-use crate::mem::read_columnar;
 use clap::Parser;
+use csvrs::executor::CatalogExecutor;
+use csvrs::index::heap::Storage;
+use csvrs::mem::{self, LoadOptions};
+use csvrs::schema::Schema;
 use rustyline::DefaultEditor;
 use rustyline::error::ReadlineError;
 use std::io::{self, Write};
 use std::path::PathBuf;
 
-use crate::query::parser::CmdParser;
-
-mod executor;
-pub mod index;
-pub mod mem;
-pub mod query;
+use csvrs::query::parser::{CmdParser, Statement};
 
 #[derive(Parser, Debug)]
 struct Args {
@@ -19,23 +16,138 @@ struct Args {
     #[arg(short, long, default_value = "~")]
     dir: String,
 
-    /// Include archives
+    /// Also load CSV files from .zip and .tar(.gz) archives found in directory
     #[arg(short, long, default_value = "false")]
-    _include_archives: bool,
+    include_archives: bool,
+
+    /// Keep parsed files in snapshots next to them (`<file>.snapshot`) and reopen those
+    /// while the file stays unchanged
+    #[arg(short, long)]
+    snapshot: bool,
+
+    /// Keep loaded data in temporary files, caching at most this many megabytes of it in
+    /// memory. Lets files larger than memory be queried
+    #[arg(long, value_name = "MB")]
+    buffer_pool: Option<usize>,
+
+    /// Field delimiter, e.g. ',', ';', '|' or 'tab'. Detected from file when not given
+    #[arg(long, value_parser = mem::parse_dialect_char)]
+    delimiter: Option<u8>,
+
+    /// Quote character. Detected from file when not given
+    #[arg(long, value_parser = mem::parse_dialect_char)]
+    quote: Option<u8>,
+
+    /// Escape character for quotes inside quoted fields, e.g. '\'
+    #[arg(long, value_parser = mem::parse_dialect_char)]
+    escape: Option<u8>,
+
+    /// Do not treat two consecutive quotes as an escaped quote
+    #[arg(long)]
+    no_double_quote: bool,
+
+    /// Skip lines starting with this character, e.g. '#'
+    #[arg(long, value_parser = mem::parse_dialect_char)]
+    comment: Option<u8>,
+
+    /// Trim whitespace around fields
+    #[arg(long)]
+    trim: bool,
+
+    /// First row holds column names. Detected from file when neither this nor --no-header given
+    #[arg(long, conflicts_with = "no_header")]
+    header: bool,
+
+    /// First row is data, columns are named c0, c1, ... unless --columns is given
+    #[arg(long)]
+    no_header: bool,
+
+    /// Comma separated column names, override the ones from header
+    #[arg(long, value_delimiter = ',')]
+    columns: Option<Vec<String>>,
+
+    /// Comma separated values read as null, e.g. 'NULL,-'. Defaults to empty value, NULL, NA
+    /// and \N
+    #[arg(long, value_delimiter = ',', value_name = "VALUES")]
+    nulls: Option<Vec<String>>,
+
+    /// File with `column: type` lines (int, float, bool, date or text) overriding inferred
+    /// column types. `table.column: type` applies to single table only
+    #[arg(long, value_name = "FILE")]
+    schema: Option<PathBuf>,
+}
+
+impl Args {
+    fn load_options(&self) -> std::io::Result<LoadOptions> {
+        Ok(LoadOptions {
+            delimiter: self.delimiter,
+            quote: self.quote,
+            escape: self.escape,
+            no_double_quote: self.no_double_quote,
+            comment: self.comment,
+            trim: self.trim,
+            has_headers: match (self.header, self.no_header) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ => None,
+            },
+            column_names: self.columns.clone(),
+            include_archives: self.include_archives,
+            snapshots: self.snapshot,
+            storage: self
+                .buffer_pool
+                .map_or(Storage::Memory, Storage::with_memory_limit),
+            schema: match &self.schema {
+                Some(path) => Schema::read(path)?,
+                None => Schema::default(),
+            },
+            nulls: self.nulls.clone(),
+        })
+    }
 }
 
-enum WorkingEnv {
-    Csv(PathBuf),
+/// Expands leading `~` to home directory, shell does not do it for default value.
+fn expand_home(dir: &str) -> PathBuf {
+    let rest = if dir == "~" {
+        Some("")
+    } else {
+        dir.strip_prefix("~/")
+    };
+    match (rest, std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => dir.into(),
+    }
 }
 
 fn main() -> std::io::Result<()> {
     let args = Args::parse();
-    let buf: PathBuf = args.dir.into();
-
-    let set = mem::index_heap_columnar(buf)?;
+    let options = args.load_options()?;
+    let buf = expand_home(&args.dir);
+    let catalog = mem::load_catalog(buf, &options)?;
 
     println!("Working set loaded.");
-    println!("Available columns: {:?}", set.columns.keys());
+    for (name, table) in &catalog.tables {
+        println!(
+            "Table {} ({}): {}",
+            name,
+            table.path.display(),
+            table.dialect
+        );
+        let mut columns: Vec<_> = table.set.columns.iter().collect();
+        columns.sort_by_key(|(_, position)| **position);
+        let columns: Vec<_> = columns
+            .into_iter()
+            .map(|(name, position)| {
+                let encoding = if table.set.is_dictionary(*position) {
+                    ", dictionary"
+                } else {
+                    ""
+                };
+                format!("{} ({}{})", name, table.set.types[*position], encoding)
+            })
+            .collect();
+        println!("  Available columns: {}", columns.join(", "));
+    }
     println!(" ('exit' to quit): ");
 
     let mut rl = DefaultEditor::new().expect("Failed to create editor");
@@ -61,7 +173,7 @@ fn main() -> std::io::Result<()> {
         }
 
         let mut out = stdout.lock();
-        let index_visitor = ColumnarExecutor { set: &set };
+        let index_visitor = CatalogExecutor { catalog: &catalog };
 
         let parsed = CmdParser::new();
         let statement = parsed.parse_string(input.trim());
@@ -73,7 +185,21 @@ fn main() -> std::io::Result<()> {
             continue;
         }
 
-        let columns = statement.unwrap().accept(&index_visitor);
+        let statement = statement.unwrap();
+        let result = statement
+            .accept(&index_visitor)
+            .and_then(|columns| Ok((columns, index_visitor.access_path(&statement)?)));
+        let (columns, access_path) = match result {
+            Ok(result) => result,
+            Err(err) => {
+                out.write_all(format!("{}\n", err).as_bytes())?;
+                continue;
+            }
+        };
+        if let Statement::Index(..) = statement {
+            out.write_all(b"Index created.\n")?;
+            continue;
+        }
 
         let mut cnt = 0;
         for c in columns {
@@ -81,10 +207,11 @@ fn main() -> std::io::Result<()> {
 
             for e in c {
                 out.write_all(b" | ")?;
-                out.write_all(read_columnar(&set, e.0, e.1)).unwrap();
+                out.write_all(&e)?;
             }
             out.write_all(b"\n")?;
         }
+        out.write_all(format!("Access path: {}\n", access_path).as_bytes())?;
         out.write_all(format!("Got {} records.", cnt).as_bytes())?;
         out.write_all(b"\n")?;
     }
@@ -1,166 +1,1141 @@
-use crate::index::heap::{BlockOffset, BufferPool};
-use std::{collections::HashMap, fs::File, io::BufReader, path::PathBuf};
+use crate::{
+    executor::ExecutionError,
+    index::{
+        ColumnIndex, IndexKind,
+        bitmap::{Bitmap, BitmapIndex},
+        hash::HashIndex,
+        heap::{BlockOffset, BlockStore, Storage, fixed_position},
+        tree::{BTree, Tid},
+        zone::ZoneMap,
+    },
+    schema::{ColumnType, ColumnTypes, Schema},
+    snapshot,
+    value::{parse_number, tuple_key},
+};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::File,
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
 
-pub(crate) struct WorkingSet {
-    pub(crate) columns: HashMap<String, usize>,
-    pub(crate) values: Vec<Vec<String>>,
+/// CSV dialect used to parse input files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dialect {
+    pub(crate) delimiter: u8,
+    pub(crate) quote: u8,
+    /// Escape character for quotes inside quoted fields, e.g. `\"`
+    pub(crate) escape: Option<u8>,
+    /// Whether two consecutive quotes inside quoted field stand for single quote
+    pub(crate) double_quote: bool,
+    /// Lines starting with this character are skipped
+    pub(crate) comment: Option<u8>,
+    /// Trim whitespace around fields
+    pub(crate) trim: bool,
+    /// Whether the first record holds column names
+    pub(crate) has_headers: bool,
+    /// Values standing for missing value (null)
+    pub(crate) nulls: Vec<String>,
 }
 
-pub(crate) struct RowWorkingSet {
-    pub(crate) rows: Vec<(usize, BlockOffset)>,
-    heap: BufferPool,
+impl Default for Dialect {
+    fn default() -> Self {
+        Dialect {
+            delimiter: b';',
+            quote: b'"',
+            escape: None,
+            double_quote: true,
+            comment: None,
+            trim: false,
+            has_headers: true,
+            nulls: DEFAULT_NULLS.iter().map(|n| n.to_string()).collect(),
+        }
+    }
 }
 
-pub(crate) struct ColumnsWorkingSet {
-    pub(crate) columns: HashMap<String, usize>,
-    pub(crate) data: Vec<BufferPool>,
-    pub(crate) rows: Vec<Vec<(usize, BlockOffset)>>,
+/// Values read as null unless other ones are given.
+pub(crate) const DEFAULT_NULLS: [&str; 4] = ["", "NULL", "NA", "\\N"];
+
+impl Dialect {
+    fn reader<R: Read>(&self, reader: R) -> csv::Reader<R> {
+        csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .escape(self.escape)
+            .double_quote(self.double_quote)
+            .comment(self.comment)
+            .has_headers(self.has_headers)
+            .trim(if self.trim {
+                csv::Trim::All
+            } else {
+                csv::Trim::None
+            })
+            .from_reader(reader)
+    }
 }
 
-pub(crate) fn read_columnar(
-    set: &ColumnsWorkingSet,
-    heap: usize,
-    ptr: (usize, BlockOffset),
-) -> &[u8] {
-    let heap = &set.data[heap];
-    heap.read(ptr.0, ptr.1).unwrap()
+/// How to load CSV files. Dialect settings left as `None` are sniffed from every file.
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    pub delimiter: Option<u8>,
+    pub quote: Option<u8>,
+    pub escape: Option<u8>,
+    pub no_double_quote: bool,
+    pub comment: Option<u8>,
+    pub trim: bool,
+    pub has_headers: Option<bool>,
+    /// Column names overriding the ones from header
+    pub column_names: Option<Vec<String>>,
+    /// Open `.zip` and `.tar(.gz)` archives found while scanning directory
+    pub include_archives: bool,
+    /// Reopen files from snapshots, see [`snapshot::load_or_build`]
+    pub snapshots: bool,
+    /// Where loaded blocks are kept
+    pub storage: Storage,
+    /// Column types overriding inferred ones
+    pub schema: Schema,
+    /// Values read as null, [`DEFAULT_NULLS`] when not given
+    pub nulls: Option<Vec<String>>,
 }
 
-pub(crate) fn index_heap_columnar(buf: PathBuf) -> std::io::Result<ColumnsWorkingSet> {
-    if buf.is_file() {
-        let mut columns = HashMap::new();
+impl LoadOptions {
+    /// Sniffs dialect of given file, settings given explicitly take precedence.
+    pub(crate) fn dialect(&self, path: &Path) -> std::io::Result<Dialect> {
+        self.dialect_of(open(path)?)
+    }
 
-        let file = File::open(buf)?;
-        let mut csv_reader = csv::ReaderBuilder::new()
-            .delimiter(b';')
-            .from_reader(BufReader::new(file));
-        if !csv_reader.has_headers() {
-            panic!("CSV without headers not supported yet.");
-        }
+    /// Same as [`LoadOptions::dialect`] for data that is not stored in plain file.
+    fn dialect_of<R: Read>(&self, reader: R) -> std::io::Result<Dialect> {
+        let detected = sniff(reader, self.delimiter, self.quote)?;
+
+        Ok(Dialect {
+            escape: self.escape,
+            double_quote: !self.no_double_quote,
+            comment: self.comment,
+            trim: self.trim,
+            has_headers: self.has_headers.unwrap_or(detected.has_headers),
+            nulls: self.nulls.clone().unwrap_or(detected.nulls),
+            ..detected
+        })
+    }
+}
+
+impl std::fmt::Display for Dialect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "delimiter {:?}, quote {:?}, header {}",
+            self.delimiter as char,
+            self.quote as char,
+            if self.has_headers { "yes" } else { "no" }
+        )
+    }
+}
+
+/// Parses single dialect character given on command line. Accepts any single ASCII character
+/// and names of characters that are awkward to pass through shell.
+pub fn parse_dialect_char(value: &str) -> Result<u8, String> {
+    match value {
+        "\\t" | "tab" => Ok(b'\t'),
+        "space" => Ok(b' '),
+        "comma" => Ok(b','),
+        "semicolon" => Ok(b';'),
+        "pipe" => Ok(b'|'),
+        _ if value.len() == 1 && value.is_ascii() => Ok(value.as_bytes()[0]),
+        _ => Err(format!("expected single ASCII character, got {:?}", value)),
+    }
+}
+
+/// Number of bytes sampled from the beginning of file when sniffing dialect.
+const SNIFF_SAMPLE_SIZE: usize = 8 * 1024;
+
+/// Number of records used to guess whether the first one is a header.
+const SNIFF_HEADER_RECORDS: usize = 20;
+
+const DELIMITER_CANDIDATES: [u8; 4] = [b',', b';', b'\t', b'|'];
+const QUOTE_CANDIDATES: [u8; 2] = [b'"', b'\''];
+
+/// Detects dialect of a CSV file by sampling its first few KB. Delimiter and quote given by
+/// caller are taken as they are, only the missing ones are detected.
+pub(crate) fn sniff<R: Read>(
+    reader: R,
+    delimiter: Option<u8>,
+    quote: Option<u8>,
+) -> std::io::Result<Dialect> {
+    let mut sample = Vec::with_capacity(SNIFF_SAMPLE_SIZE);
+    reader
+        .take(SNIFF_SAMPLE_SIZE as u64)
+        .read_to_end(&mut sample)?;
+
+    // Last line is most likely cut in half, unless whole file fits into the sample
+    if sample.len() == SNIFF_SAMPLE_SIZE
+        && let Some(end) = sample.iter().rposition(|b| *b == b'\n')
+    {
+        sample.truncate(end + 1);
+    }
+
+    Ok(sniff_sample(&sample, delimiter, quote))
+}
+
+fn sniff_sample(sample: &[u8], delimiter: Option<u8>, quote: Option<u8>) -> Dialect {
+    let mut dialect = Dialect::default();
+    dialect.quote = quote.unwrap_or_else(|| detect_quote(sample));
+    dialect.delimiter = delimiter
+        .or_else(|| detect_delimiter(sample, dialect.quote))
+        .unwrap_or(dialect.delimiter);
+
+    let records: Vec<csv::ByteRecord> = Dialect {
+        has_headers: false,
+        ..dialect.clone()
+    }
+    .reader(sample)
+    .into_byte_records()
+    .take(SNIFF_HEADER_RECORDS)
+    .map_while(Result::ok)
+    .collect();
+    dialect.has_headers = guess_has_headers(&records);
+
+    dialect
+}
 
-        csv_reader.headers().into_iter().for_each(|value| {
-            for (i, v) in value.iter().enumerate() {
-                columns.insert(v.trim().to_string(), i);
+/// Picks quote character that most often opens or closes a field.
+fn detect_quote(sample: &[u8]) -> u8 {
+    let is_boundary = |b: Option<&u8>| {
+        b.is_none_or(|b| matches!(b, b'\n' | b'\r') || DELIMITER_CANDIDATES.contains(b))
+    };
+
+    QUOTE_CANDIDATES
+        .iter()
+        .map(|&quote| {
+            let count = sample
+                .iter()
+                .enumerate()
+                .filter(|(i, b)| {
+                    **b == quote
+                        && (is_boundary(i.checked_sub(1).and_then(|i| sample.get(i)))
+                            || is_boundary(sample.get(i + 1)))
+                })
+                .count();
+            (count, quote)
+        })
+        .filter(|(count, _)| *count > 0)
+        // On tie prefer the first candidate
+        .max_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)))
+        .map_or(b'"', |(_, quote)| quote)
+}
+
+/// Picks delimiter that splits most records into the same number of fields.
+fn detect_delimiter(sample: &[u8], quote: u8) -> Option<u8> {
+    DELIMITER_CANDIDATES
+        .iter()
+        .filter_map(|&delimiter| {
+            let counts = delimiters_per_record(sample, delimiter, quote);
+
+            let mut frequencies: HashMap<usize, usize> = HashMap::new();
+            for count in &counts {
+                *frequencies.entry(*count).or_default() += 1;
+            }
+            let (mode, matching) = frequencies
+                .into_iter()
+                .max_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)))?;
+            if mode == 0 {
+                return None;
             }
-        });
 
-        let mut data = Vec::with_capacity(columns.len());
-        for _ in 0..columns.len() {
-            data.push(BufferPool::new());
-        }
+            let consistency = matching as f64 / counts.len() as f64;
+            Some((consistency, mode, delimiter))
+        })
+        // On tie prefer delimiter producing more fields
+        .max_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)))
+        .map(|(_, _, delimiter)| delimiter)
+}
 
-        let mut rows = Vec::with_capacity(columns.len());
-        for values in csv_reader.records() {
-            let mut row = vec![];
-            let record = values?;
+/// Counts delimiters outside of quotes in every non empty record.
+fn delimiters_per_record(sample: &[u8], delimiter: u8, quote: u8) -> Vec<usize> {
+    let mut counts = vec![];
+    let mut count = 0;
+    let mut in_quotes = false;
+    let mut empty = true;
 
-            for (i, value) in record.iter().enumerate() {
-                row.push(data[i].allocate(value.as_bytes()));
+    for &b in sample {
+        if b == quote {
+            in_quotes = !in_quotes;
+        } else if in_quotes {
+            // Delimiters and new lines inside quotes are part of the value
+        } else if b == b'\n' {
+            if !empty {
+                counts.push(count);
             }
-            rows.push(row);
+            count = 0;
+            empty = true;
+            continue;
+        } else if b == delimiter {
+            count += 1;
+        } else if b == b'\r' {
+            continue;
         }
+        empty = false;
+    }
+    if !empty {
+        counts.push(count);
+    }
+
+    counts
+}
+
+/// Guesses whether the first record is a header. Every column whose values share a type
+/// (numeric) or a length votes whether the first record matches it. Undecided means header.
+fn guess_has_headers(records: &[csv::ByteRecord]) -> bool {
+    let Some((first, rest)) = records.split_first() else {
+        return true;
+    };
+    if rest.is_empty() {
+        return true;
+    }
+
+    let mut votes = 0i32;
+    for (col, head) in first.iter().enumerate() {
+        let values: Vec<&[u8]> = rest.iter().filter_map(|r| r.get(col)).collect();
+        if values.is_empty() {
+            continue;
+        }
+
+        if values.iter().all(|v| parse_number(v).is_some()) {
+            votes += if parse_number(head).is_some() { -1 } else { 1 };
+        } else if values.iter().all(|v| v.len() == values[0].len()) {
+            votes += if head.len() == values[0].len() { -1 } else { 1 };
+        }
+    }
+
+    votes >= 0
+}
+
+/// Size of code of dictionary encoded value.
+pub(crate) const CODE_WIDTH: usize = size_of::<u16>();
+
+/// Distinct values of dictionary encoded text column. Column heap holds code of every row,
+/// values themselves are stored once in heap of their own.
+pub(crate) struct Dictionary {
+    pub(crate) heap: Box<dyn BlockStore>,
+    /// Pointers to values, indexed by code
+    pub(crate) values: Vec<(usize, BlockOffset)>,
+}
+
+impl Dictionary {
+    fn new(heap: Box<dyn BlockStore>) -> Dictionary {
+        Dictionary {
+            heap,
+            values: vec![],
+        }
+    }
 
-        Ok(ColumnsWorkingSet {
+    /// Value of stored code.
+    pub(crate) fn value(&self, code: &[u8]) -> std::io::Result<Cow<'_, [u8]>> {
+        let (block_id, offset) = self.values[decode_code(code)];
+        self.heap.read(block_id, offset)
+    }
+
+    /// Every distinct value, in order of their codes.
+    pub(crate) fn values(&self) -> impl Iterator<Item = std::io::Result<Cow<'_, [u8]>>> {
+        self.values
+            .iter()
+            .map(|(block_id, offset)| self.heap.read(*block_id, *offset))
+    }
+}
+
+pub(crate) fn decode_code(code: &[u8]) -> usize {
+    u16::from_le_bytes(code.try_into().unwrap()) as usize
+}
+
+pub struct ColumnsWorkingSet {
+    pub columns: HashMap<String, usize>,
+    pub types: Vec<ColumnType>,
+    pub(crate) data: Vec<Box<dyn BlockStore>>,
+    /// Pointers to values of plain text columns of every row. Values of fixed width columns
+    /// and codes of dictionary encoded ones are found by row id, see [`fixed_position`].
+    /// Loading fails past [`MAX_ROWS`], so that row ids fit into `u32` of bitmaps and indexes.
+    pub rows: Vec<Vec<(usize, BlockOffset)>>,
+    /// Position of column's pointer in row, `None` for fixed width and dictionary columns
+    slots: Vec<Option<usize>>,
+    /// Rows holding null, per column. Their stored value is just a placeholder.
+    pub(crate) nulls: Vec<Bitmap>,
+    /// Values of dictionary encoded columns
+    pub(crate) dictionaries: Vec<Option<Dictionary>>,
+    /// Zones of blocks of fixed width columns
+    pub(crate) zones: Vec<Option<ZoneMap>>,
+    /// Indexes with ids of columns they are built on. Created from REPL while set is shared
+    /// by queries, possibly running on other threads. Everything else is immutable once loaded.
+    pub(crate) indexes: RwLock<Vec<(Vec<usize>, ColumnIndex)>>,
+}
+
+impl ColumnsWorkingSet {
+    pub(crate) fn new(
+        columns: HashMap<String, usize>,
+        types: Vec<ColumnType>,
+        data: Vec<Box<dyn BlockStore>>,
+        rows: Vec<Vec<(usize, BlockOffset)>>,
+        nulls: Vec<Bitmap>,
+        dictionaries: Vec<Option<Dictionary>>,
+        zones: Vec<Option<ZoneMap>>,
+    ) -> ColumnsWorkingSet {
+        ColumnsWorkingSet {
             columns,
+            slots: slots(&types, &dictionaries),
+            types,
             data,
             rows,
+            nulls,
+            dictionaries,
+            zones,
+            indexes: RwLock::default(),
+        }
+    }
+
+    /// Where value of given row and column is stored, code of the value for dictionary column.
+    pub(crate) fn pointer(&self, row: usize, column: usize) -> (usize, BlockOffset) {
+        match (self.slots[column], self.width(column)) {
+            (Some(slot), _) => self.rows[row][slot],
+            (None, Some(width)) => fixed_position(width, row),
+            (None, None) => unreachable!("Text column without pointers"),
+        }
+    }
+
+    /// Whether column's values are kept in dictionary.
+    pub fn is_dictionary(&self, column: usize) -> bool {
+        self.dictionaries[column].is_some()
+    }
+
+    /// Width of values stored in column heap, `None` when they vary.
+    fn width(&self, column: usize) -> Option<usize> {
+        match self.dictionaries[column] {
+            Some(_) => Some(CODE_WIDTH),
+            None => self.types[column].width(),
+        }
+    }
+
+    pub(crate) fn is_null(&self, row: usize, column: usize) -> bool {
+        self.nulls[column].contains(row as u32)
+    }
+
+    /// Bytes stored in column heap: code for dictionary column, value otherwise. `None` for
+    /// null.
+    pub(crate) fn stored(
+        &self,
+        row: usize,
+        column: usize,
+    ) -> std::io::Result<Option<Cow<'_, [u8]>>> {
+        if self.is_null(row, column) {
+            return Ok(None);
+        }
+        let (block_id, offset) = self.pointer(row, column);
+        self.data[column].read(block_id, offset).map(Some)
+    }
+
+    /// Stored value, encoded according to column type. `None` for null.
+    pub(crate) fn value(
+        &self,
+        row: usize,
+        column: usize,
+    ) -> std::io::Result<Option<Cow<'_, [u8]>>> {
+        let Some(stored) = self.stored(row, column)? else {
+            return Ok(None);
+        };
+        match &self.dictionaries[column] {
+            Some(dictionary) => dictionary.value(&stored).map(Some),
+            None => Ok(Some(stored)),
+        }
+    }
+
+    /// Value as text, the way it is shown.
+    pub(crate) fn text(&self, row: usize, column: usize) -> std::io::Result<Option<Cow<'_, [u8]>>> {
+        Ok(self
+            .value(row, column)?
+            .map(|v| self.types[column].format(v)))
+    }
+
+    /// Builds index over values of given columns, replacing existing one of the same kind.
+    /// Only B-tree can span several columns, its keys are tuples of their values. Nulls are
+    /// keyed as empty text in B-tree, whose lookups are always rechecked, and left out of
+    /// hash and bitmap indexes.
+    /// Index is built aside, queries keep running until it is swapped in under write latch.
+    pub(crate) fn create_index(&self, columns: &[usize], kind: IndexKind) -> std::io::Result<()> {
+        if kind != IndexKind::BTree && columns.len() > 1 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} index can't span several columns", kind),
+            ));
+        }
+        let rows = 0..self.rows.len();
+        let index = match kind {
+            // Keyed by text, typed values order the same way there
+            IndexKind::BTree => {
+                let mut tree = BTree::new();
+                for row in rows {
+                    let texts = columns
+                        .iter()
+                        .map(|c| Ok(self.text(row, *c)?.unwrap_or_default()))
+                        .collect::<std::io::Result<Vec<_>>>()?;
+                    let (block_id, offset) = self.pointer(row, columns[0]);
+                    let tid = Tid {
+                        block_id,
+                        offset,
+                        row,
+                    };
+                    tree.insert(&tuple_key(&texts), tid);
+                }
+                ColumnIndex::BTree(tree)
+            }
+            IndexKind::Hash => {
+                let mut hash = HashIndex::new();
+                for row in rows {
+                    if let Some(value) = self.value(row, columns[0])? {
+                        hash.insert(&value, row);
+                    }
+                }
+                ColumnIndex::Hash(hash)
+            }
+            IndexKind::Bitmap => {
+                let mut bitmap = BitmapIndex::new();
+                for row in rows {
+                    if let Some(value) = self.value(row, columns[0])? {
+                        bitmap.insert(&value, row as u32);
+                    }
+                }
+                ColumnIndex::Bitmap(bitmap)
+            }
+        };
+
+        let mut indexes = self.indexes.write().unwrap();
+        indexes.retain(|(cols, existing)| cols != columns || existing.kind() != kind);
+        indexes.push((columns.to_vec(), index));
+        Ok(())
+    }
+}
+
+/// Positions of pointers of plain text columns in rows.
+fn slots(types: &[ColumnType], dictionaries: &[Option<Dictionary>]) -> Vec<Option<usize>> {
+    let mut next = 0;
+    types
+        .iter()
+        .zip(dictionaries)
+        .map(|(ty, dictionary)| {
+            (ty.width().is_none() && dictionary.is_none()).then(|| {
+                next += 1;
+                next - 1
+            })
         })
-    } else {
-        panic!("Path is not a file. Directory scanning not implemented yet.");
+        .collect()
+}
+
+/// Maps column names to their positions. Names given by caller take precedence over the ones
+/// from header, columns left without a name are called `c0`, `c1`, ...
+fn column_names<R: Read>(
+    csv_reader: &mut csv::Reader<R>,
+    names: Option<&[String]>,
+) -> csv::Result<HashMap<String, usize>> {
+    let has_headers = csv_reader.has_headers();
+    // Without headers this is the first record, which is not consumed
+    let header = csv_reader.headers()?;
+
+    let mut columns = HashMap::new();
+    for (i, value) in header.iter().enumerate() {
+        let name = match names.and_then(|names| names.get(i)) {
+            Some(name) => name.trim().to_string(),
+            None if has_headers => value.trim().to_string(),
+            None => format!("c{}", i),
+        };
+        columns.insert(name, i);
     }
+
+    Ok(columns)
 }
 
-pub(crate) fn index_heap_row(buf: PathBuf) -> std::io::Result<RowWorkingSet> {
-    let mut heap = BufferPool::new();
+/// Number of leading records column types are inferred from.
+const INFER_SAMPLE_RECORDS: usize = 1000;
 
-    if buf.is_file() {
-        let file = File::open(buf)?;
+/// Text column is dictionary encoded while it holds at most one distinct value per this many
+/// rows.
+const DICTIONARY_RATIO: usize = 4;
 
-        let mut csv_reader = csv::ReaderBuilder::new()
-            .delimiter(b';')
-            .from_reader(BufReader::new(file));
+/// Most rows a working set holds, their ids are stored as `u32`.
+pub(crate) const MAX_ROWS: u64 = u32::MAX as u64 + 1;
 
-        let mut columns = HashMap::new();
-        if !csv_reader.has_headers() {
-            panic!("CSV without headers not supported yet.");
-        }
+/// Loads CSV file into columnar working set. `names` override column names taken from header,
+/// `schema` overrides inferred column types. Blocks of every column heap are kept in given
+/// storage.
+pub fn index_heap_columnar(
+    buf: PathBuf,
+    dialect: &Dialect,
+    names: Option<&[String]>,
+    schema: &ColumnTypes,
+    storage: Storage,
+) -> std::io::Result<ColumnsWorkingSet> {
+    load_columnar(open(&buf)?, dialect, names, schema, storage)
+}
+
+/// Types not given by schema are inferred from first records. Column with inferred type turns
+/// into text once value of other type shows up later, such value is an error when the type
+/// was given by schema. Null values fit any type. Zones of blocks are recorded for columns
+/// that keep fixed width type.
+///
+/// Text columns with few distinct values in first records are dictionary encoded, they turn
+/// plain once distinct values get too many, see [`DICTIONARY_RATIO`].
+fn load_columnar<R: Read>(
+    reader: R,
+    dialect: &Dialect,
+    names: Option<&[String]>,
+    schema: &ColumnTypes,
+    storage: Storage,
+) -> std::io::Result<ColumnsWorkingSet> {
+    let mut csv_reader = dialect.reader(reader);
+    let columns = column_names(&mut csv_reader, names)?;
+    let width = csv_reader.headers()?.len();
+
+    let mut records = csv_reader.records();
+    let sample = records
+        .by_ref()
+        .take(INFER_SAMPLE_RECORDS)
+        .collect::<csv::Result<Vec<_>>>()?;
 
-        csv_reader.headers().into_iter().for_each(|value| {
-            for (i, v) in value.iter().enumerate() {
-                columns.insert(v.trim().to_string(), i);
+    let mut given = vec![None; width];
+    for (name, column) in &columns {
+        given[*column] = schema.get(name).map(|ty| (name, *ty));
+    }
+    let mut types: Vec<ColumnType> = given
+        .iter()
+        .enumerate()
+        .map(|(column, given)| match given {
+            Some((_, ty)) => *ty,
+            None => {
+                let values: Vec<&str> = sample
+                    .iter()
+                    .map(|r| &r[column])
+                    .filter(|v| !is_null(dialect, v))
+                    .collect();
+                ColumnType::infer(&values)
             }
+        })
+        .collect();
+
+    let mut data = Vec::with_capacity(width);
+    let mut dictionaries = Vec::with_capacity(width);
+    for (column, ty) in types.iter().enumerate() {
+        data.push(storage.create(width)?);
+
+        let distinct: HashSet<&str> = sample
+            .iter()
+            .map(|r| &r[column])
+            .filter(|v| !is_null(dialect, v))
+            .collect();
+        let encode = *ty == ColumnType::Text
+            && !sample.is_empty()
+            && distinct.len() * DICTIONARY_RATIO <= sample.len();
+        dictionaries.push(if encode {
+            Some(Dictionary::new(storage.create(width)?))
+        } else {
+            None
         });
+    }
+    // Codes of values in dictionaries, needed only while loading
+    let mut codes: Vec<HashMap<Vec<u8>, u16>> = vec![HashMap::new(); width];
 
-        let mut rows = vec![];
-        for values in csv_reader.records() {
-            let v = values?;
-            let bytes = v.into_byte_record();
+    let mut rows = Vec::with_capacity(sample.len());
+    let mut nulls = vec![Bitmap::new(); width];
+    let mut zones: Vec<Option<ZoneMap>> = types
+        .iter()
+        .map(|ty| ty.width().map(|_| ZoneMap::new(*ty)))
+        .collect();
+    for record in sample.into_iter().map(Ok).chain(records) {
+        let record = record?;
+        let row_id = row_id(rows.len())?;
+        let mut row = vec![];
 
-            rows.push(heap.allocate(bytes.as_slice()));
+        for (i, value) in record.iter().enumerate() {
+            let null = is_null(dialect, value);
+            if null {
+                nulls[i].insert(row_id);
+            }
+            if let Some(dictionary) = &mut dictionaries[i] {
+                let code = if null {
+                    Some(0)
+                } else {
+                    dictionary_code(dictionary, &mut codes[i], value, rows.len())?
+                };
+                if let Some(code) = code {
+                    let ptr = data[i].allocate(&code.to_le_bytes())?;
+                    debug_assert_eq!(ptr, fixed_position(CODE_WIDTH, rows.len()));
+                    continue;
+                }
+                make_plain(
+                    i,
+                    &types,
+                    &mut data,
+                    &mut dictionaries,
+                    &mut rows,
+                    &nulls[i],
+                )?;
+                codes[i] = HashMap::new();
+            }
+            let Some(width) = types[i].width() else {
+                row.push(data[i].allocate(if null { b"" } else { value.as_bytes() })?);
+                continue;
+            };
+            let encoded = if null {
+                Some(vec![0; width])
+            } else if given[i].is_some() {
+                types[i].parse(value.as_bytes())
+            } else {
+                types[i].encode(value.as_bytes())
+            };
+            match encoded {
+                Some(encoded) => {
+                    let ptr = data[i].allocate(&encoded)?;
+                    debug_assert_eq!(ptr, fixed_position(width, rows.len()));
+                    if let Some(zones) = zones[i].as_mut().filter(|_| !null) {
+                        zones.record(ptr.0, &encoded);
+                    }
+                }
+                None => {
+                    if let Some((name, ty)) = given[i] {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!("Value {:?} of column {} is not {}", value, name, ty),
+                        ));
+                    }
+                    zones[i] = None;
+                    make_text(
+                        i,
+                        &mut types,
+                        &mut data,
+                        &dictionaries,
+                        &mut rows,
+                        &nulls[i],
+                        storage,
+                    )?;
+                    row.push(data[i].allocate(value.as_bytes())?);
+                }
+            }
         }
+        rows.push(row);
+    }
 
-        Ok(RowWorkingSet { rows, heap })
-    } else {
-        panic!("Path is not a file. Directory scanning not implemented yet.");
+    Ok(ColumnsWorkingSet::new(
+        columns,
+        types,
+        data,
+        rows,
+        nulls,
+        dictionaries,
+        zones,
+    ))
+}
+
+/// Code of value, new values are added to dictionary. `None` when value does not fit, as
+/// dictionary would grow past [`DICTIONARY_RATIO`] of rows loaded so far or out of codes.
+/// The ratio is not checked within first records, those were checked all at once.
+fn dictionary_code(
+    dictionary: &mut Dictionary,
+    codes: &mut HashMap<Vec<u8>, u16>,
+    value: &str,
+    row: usize,
+) -> std::io::Result<Option<u16>> {
+    if let Some(code) = codes.get(value.as_bytes()) {
+        return Ok(Some(*code));
+    }
+
+    let Ok(code) = u16::try_from(dictionary.values.len()) else {
+        return Ok(None);
+    };
+    if row >= INFER_SAMPLE_RECORDS && (code as usize + 1) * DICTIONARY_RATIO > row + 1 {
+        return Ok(None);
     }
+    dictionary
+        .values
+        .push(dictionary.heap.allocate(value.as_bytes())?);
+    codes.insert(value.as_bytes().to_vec(), code);
+    Ok(Some(code))
+}
+
+/// Position of pointer of text column in rows loaded so far.
+fn slot(column: usize, types: &[ColumnType], dictionaries: &[Option<Dictionary>]) -> usize {
+    slots(&types[..column], &dictionaries[..column])
+        .iter()
+        .flatten()
+        .count()
 }
 
-pub(crate) fn read_all(set: &RowWorkingSet) {
-    for (block_id, offset) in &set.rows {
-        if let Some(bytes) = set.heap.read(*block_id, *offset) {
-            println!("{}", str::from_utf8(bytes).unwrap());
+/// Turns dictionary encoded column into plain text column. Rows loaded so far get pointers
+/// to values in dictionary, its heap becomes heap of the column.
+fn make_plain(
+    column: usize,
+    types: &[ColumnType],
+    data: &mut [Box<dyn BlockStore>],
+    dictionaries: &mut [Option<Dictionary>],
+    rows: &mut [Vec<(usize, BlockOffset)>],
+    nulls: &Bitmap,
+) -> std::io::Result<()> {
+    let slot = slot(column, types, dictionaries);
+    let mut dictionary = dictionaries[column].take().expect("Column is not encoded");
+
+    for (i, row) in rows.iter_mut().enumerate() {
+        let ptr = if nulls.contains(i as u32) {
+            dictionary.heap.allocate(b"")?
         } else {
-            panic!("Failed to serialize bytes to utf8");
-        }
+            let (block_id, offset) = fixed_position(CODE_WIDTH, i);
+            dictionary.values[decode_code(&data[column].read(block_id, offset)?)]
+        };
+        row.insert(slot, ptr);
     }
+
+    data[column] = dictionary.heap;
+    Ok(())
 }
 
-pub(crate) fn index(buf: PathBuf) -> std::io::Result<WorkingSet> {
-    println!("Opening working set from {:?}", buf);
+/// Id of row at given position, error once there are more rows than bitmaps can hold.
+fn row_id(row: usize) -> std::io::Result<u32> {
+    u32::try_from(row).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("File has more than {} rows", MAX_ROWS),
+        )
+    })
+}
 
-    if buf.is_file() {
-        let file = File::open(buf)?;
+fn is_null(dialect: &Dialect, value: &str) -> bool {
+    dialect.nulls.iter().any(|null| null == value)
+}
 
-        let mut csv_reader = csv::ReaderBuilder::new()
-            .delimiter(b';')
-            .from_reader(BufReader::new(file));
+/// Turns fixed width column into text column, moving values loaded so far into new heap.
+fn make_text(
+    column: usize,
+    types: &mut [ColumnType],
+    data: &mut [Box<dyn BlockStore>],
+    dictionaries: &[Option<Dictionary>],
+    rows: &mut [Vec<(usize, BlockOffset)>],
+    nulls: &Bitmap,
+    storage: Storage,
+) -> std::io::Result<()> {
+    let ty = types[column];
+    let width = ty.width().expect("Column is text already");
+    let slot = slot(column, types, dictionaries);
+
+    let mut heap = storage.create(data.len())?;
+    for (i, row) in rows.iter_mut().enumerate() {
+        let ptr = if nulls.contains(i as u32) {
+            heap.allocate(b"")?
+        } else {
+            let (block_id, offset) = fixed_position(width, i);
+            heap.allocate(&ty.format(data[column].read(block_id, offset)?))?
+        };
+        row.insert(slot, ptr);
+    }
+
+    data[column] = heap;
+    types[column] = ColumnType::Text;
+    Ok(())
+}
+
+/// Loaded CSV file.
+pub struct Table {
+    pub path: PathBuf,
+    pub dialect: Dialect,
+    pub set: ColumnsWorkingSet,
+}
+
+/// Tables loaded from scanned path, keyed by file stem.
+#[derive(Default)]
+pub struct Catalog {
+    pub tables: BTreeMap<String, Table>,
+}
+
+impl Catalog {
+    /// Working set selected by `@ table`. Without table name there must be exactly one table.
+    pub(crate) fn table(&self, name: Option<&str>) -> Result<&ColumnsWorkingSet, ExecutionError> {
+        match name {
+            Some(name) => match self.tables.get(name) {
+                Some(table) => Ok(&table.set),
+                None => Err(ExecutionError(format!("Missing table {}", name))),
+            },
+            None if self.tables.len() == 1 => Ok(&self.tables.values().next().unwrap().set),
+            None => Err(ExecutionError(
+                "Table must be selected with @ when more than one is loaded".to_string(),
+            )),
+        }
+    }
+
+    /// Whether table of given name is loaded already, warning that file at `path` is skipped.
+    fn is_taken(&self, name: &str, path: &Path) -> bool {
+        let Some(existing) = self.tables.get(name) else {
+            return false;
+        };
+        eprintln!(
+            "Warning: skipping {}, table {:?} is loaded from {} already",
+            path.display(),
+            name,
+            existing.path.display()
+        );
+        true
+    }
+}
+
+/// Loads single CSV file or every `.csv` file found under directory, each one as its own table.
+/// Dialect is detected per file. Snapshots are used for plain files only, archive members are
+/// always parsed. Archives found while scanning are opened only with
+/// `include_archives`, archive given directly is always opened. Files found while scanning
+/// that fail to load are skipped with warning, file given directly fails the load.
+pub fn load_catalog(path: PathBuf, options: &LoadOptions) -> std::io::Result<Catalog> {
+    let mut catalog = Catalog::default();
+    if !path.is_dir() {
+        load_file(path, options, &mut catalog)?;
+        return Ok(catalog);
+    }
 
-        let mut columns = HashMap::new();
-        if !csv_reader.has_headers() {
-            panic!("CSV without headers not supported yet.");
+    let mut files = vec![];
+    scan_dir(&path, options.include_archives, &mut files)?;
+    for path in files {
+        if let Err(error) = load_file(path.clone(), options, &mut catalog) {
+            eprintln!("Warning: skipping {}: {}", path.display(), error);
         }
+    }
+    Ok(catalog)
+}
+
+/// Loads CSV file, or every CSV file in archive, into catalog.
+fn load_file(path: PathBuf, options: &LoadOptions, catalog: &mut Catalog) -> std::io::Result<()> {
+    if let Some(archive) = Archive::of(&file_name(&path)) {
+        return load_archive(&path, archive, options, catalog);
+    }
+
+    let name = table_name(&path);
+    if catalog.is_taken(&name, &path) {
+        return Ok(());
+    }
+    let dialect = options.dialect(&path)?;
+    let names = options.column_names.as_deref();
+    let schema = options.schema.table(&name);
+    let set = if options.snapshots {
+        snapshot::load_or_build(&path, &dialect, names, &schema, options.storage)?
+    } else {
+        index_heap_columnar(path.clone(), &dialect, names, &schema, options.storage)?
+    };
+    catalog.tables.insert(name, Table { path, dialect, set });
+    Ok(())
+}
 
-        csv_reader.headers().into_iter().for_each(|value| {
-            for (i, v) in value.iter().enumerate() {
-                columns.insert(v.trim().to_string(), i);
+/// Recursively collects CSV files (and archives when asked to), sorted so that tables are
+/// loaded in stable order. Symbolic links to directories are not followed, so that links
+/// back up the tree don't loop. Subdirectories that can't be read are skipped with warning.
+fn scan_dir(dir: &Path, include_archives: bool, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            Some((entry.path(), entry.file_type().ok()?))
+        })
+        .collect::<Vec<_>>();
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    for (path, file_type) in entries {
+        let name = file_name(&path);
+        if file_type.is_dir() {
+            if let Err(error) = scan_dir(&path, include_archives, files) {
+                eprintln!("Warning: skipping {}: {}", path.display(), error);
             }
-        });
+        } else if (file_type.is_file() || file_type.is_symlink() && path.is_file())
+            && (csv_stem(&name).is_some() || include_archives && Archive::of(&name).is_some())
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
 
-        let mut rows = vec![];
-        for values in csv_reader.records() {
-            let mut row = vec![];
-            let record = values?;
-            for value in record.iter() {
-                row.push(value.to_string());
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Table name of file, e.g. `orders` for `data/orders.csv.gz`.
+fn table_name(path: &Path) -> String {
+    match csv_stem(&file_name(path)) {
+        Some(stem) => stem.to_string(),
+        None => path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    }
+}
+
+/// Name of CSV file or archive member without directories and extensions. `None` when it is
+/// not a (possibly compressed) `.csv` file.
+fn csv_stem(name: &str) -> Option<&str> {
+    let name = name.rsplit('/').next().unwrap_or(name);
+    let (name, _) = Compression::split(name);
+    let split = name.len().checked_sub(".csv".len())?;
+
+    name.get(split..)?
+        .eq_ignore_ascii_case(".csv")
+        .then(|| &name[..split])
+        .filter(|stem| !stem.is_empty())
+}
+
+/// Compression of input, recognized by file extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Compression {
+    const EXTENSIONS: [(&str, Compression); 3] = [
+        (".gz", Compression::Gzip),
+        (".zst", Compression::Zstd),
+        (".bz2", Compression::Bzip2),
+    ];
+
+    /// Splits compression extension off file name, e.g. `orders.csv.gz` gives `orders.csv`.
+    fn split(name: &str) -> (&str, Compression) {
+        for (ext, compression) in Self::EXTENSIONS {
+            if let Some(split) = name.len().checked_sub(ext.len())
+                && name
+                    .get(split..)
+                    .is_some_and(|e| e.eq_ignore_ascii_case(ext))
+            {
+                return (&name[..split], compression);
             }
-            rows.push(row);
         }
+        (name, Compression::None)
+    }
 
-        Ok(WorkingSet {
-            columns,
-            values: rows,
+    fn decoder<'r, R: Read + 'r>(self, reader: R) -> std::io::Result<Box<dyn Read + 'r>> {
+        Ok(match self {
+            Compression::None => Box::new(reader),
+            Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            Compression::Zstd => Box::new(zstd::Decoder::new(reader)?),
+            Compression::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
         })
-    } else {
-        panic!("Path is not a file. Directory scanning not implemented yet.");
     }
 }
 
+/// Opens file for reading, decompressing it when its name ends with `.gz`, `.zst` or `.bz2`.
+pub(crate) fn open(path: &Path) -> std::io::Result<Box<dyn Read>> {
+    let (_, compression) = Compression::split(&file_name(path));
+    compression.decoder(BufReader::new(File::open(path)?))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Archive {
+    Zip,
+    Tar(Compression),
+}
+
+impl Archive {
+    fn of(name: &str) -> Option<Archive> {
+        let lower = name.to_ascii_lowercase();
+        let (base, compression) = Compression::split(&lower);
+
+        if lower.ends_with(".zip") {
+            Some(Archive::Zip)
+        } else if lower.ends_with(".tgz") {
+            Some(Archive::Tar(Compression::Gzip))
+        } else if base.ends_with(".tar") {
+            Some(Archive::Tar(compression))
+        } else {
+            None
+        }
+    }
+}
+
+/// Registers every CSV member of archive as a table. Members are decompressed into memory,
+/// since dialect sniffing needs to read beginning of the data twice.
+fn load_archive(
+    path: &Path,
+    archive: Archive,
+    options: &LoadOptions,
+    catalog: &mut Catalog,
+) -> std::io::Result<()> {
+    match archive {
+        Archive::Zip => {
+            let mut zip = zip::ZipArchive::new(BufReader::new(File::open(path)?))?;
+            for i in 0..zip.len() {
+                let member = zip.by_index(i)?;
+                if member.is_file() {
+                    let name = member.name().to_string();
+                    load_member(path, &name, member, options, catalog)?;
+                }
+            }
+        }
+        Archive::Tar(compression) => {
+            let reader = compression.decoder(BufReader::new(File::open(path)?))?;
+            let mut tar = tar::Archive::new(reader);
+            for entry in tar.entries()? {
+                let entry = entry?;
+                if entry.header().entry_type().is_file() {
+                    let name = entry.path()?.to_string_lossy().into_owned();
+                    load_member(path, &name, entry, options, catalog)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn load_member<R: Read>(
+    archive: &Path,
+    name: &str,
+    reader: R,
+    options: &LoadOptions,
+    catalog: &mut Catalog,
+) -> std::io::Result<()> {
+    let Some(stem) = csv_stem(name) else {
+        return Ok(());
+    };
+    let path = archive.join(name);
+    if catalog.is_taken(stem, &path) {
+        return Ok(());
+    }
+
+    let mut data = vec![];
+    let (_, compression) = Compression::split(name);
+    compression.decoder(reader)?.read_to_end(&mut data)?;
+
+    let dialect = options.dialect_of(data.as_slice())?;
+    let names = options.column_names.as_deref();
+    let schema = options.schema.table(stem);
+    let set = load_columnar(data.as_slice(), &dialect, names, &schema, options.storage)?;
+    catalog
+        .tables
+        .insert(stem.to_string(), Table { path, dialect, set });
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::index::heap::fixed_rows;
     use std::io::Write;
     use tempfile::NamedTempFile;
 
+    /// Header and values of every record, as parsed with dialect.
+    struct WorkingSet {
+        columns: HashMap<String, usize>,
+        values: Vec<Vec<String>>,
+    }
+
+    fn index(buf: PathBuf, dialect: &Dialect) -> std::io::Result<WorkingSet> {
+        let mut csv_reader = dialect.reader(open(&buf)?);
+        let columns = column_names(&mut csv_reader, None)?;
+        let values = csv_reader
+            .records()
+            .map(|record| Ok(record?.iter().map(str::to_string).collect()))
+            .collect::<std::io::Result<_>>()?;
+        Ok(WorkingSet { columns, values })
+    }
+
     #[test]
     fn test_index_valid_csv_file() -> std::io::Result<()> {
         let mut file = NamedTempFile::new()?;
@@ -168,7 +1143,7 @@ mod tests {
         writeln!(file, "Alice;25;NYC")?;
         writeln!(file, "Bob;30;LA")?;
 
-        let working_set = index(file.path().to_path_buf())?;
+        let working_set = index(file.path().to_path_buf(), &Dialect::default())?;
 
         assert_eq!(working_set.columns.len(), 3);
         assert_eq!(working_set.values.len(), 2);
@@ -184,7 +1159,7 @@ mod tests {
         writeln!(file, "first;second;third")?;
         writeln!(file, "a;b;c")?;
 
-        let working_set = index(file.path().to_path_buf())?;
+        let working_set = index(file.path().to_path_buf(), &Dialect::default())?;
 
         assert_eq!(*working_set.columns.get("first").unwrap(), 0);
         assert_eq!(*working_set.columns.get("second").unwrap(), 1);
@@ -198,7 +1173,7 @@ mod tests {
         writeln!(file, " name ; age ; city ")?; // Headers with spaces
         writeln!(file, "Alice;25;NYC")?;
 
-        let working_set = index(file.path().to_path_buf())?;
+        let working_set = index(file.path().to_path_buf(), &Dialect::default())?;
 
         assert!(working_set.columns.contains_key("name"));
         assert!(working_set.columns.contains_key("age"));
@@ -214,7 +1189,7 @@ mod tests {
         writeln!(file, "Alice;100")?;
         writeln!(file, "Bob;95")?;
 
-        let working_set = index(file.path().to_path_buf())?;
+        let working_set = index(file.path().to_path_buf(), &Dialect::default())?;
 
         let name_idx = *working_set.columns.get("name").unwrap();
         let score_idx = *working_set.columns.get("score").unwrap();
@@ -226,10 +1201,835 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Path is not a file. Directory scanning not implemented yet.")]
+    fn test_comma_delimiter_with_quoted_fields() -> std::io::Result<()> {
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "name,city")?;
+        writeln!(file, "\"Doe, John\",\"New \"\"Big\"\" York\"")?;
+
+        let dialect = Dialect {
+            delimiter: b',',
+            ..Dialect::default()
+        };
+        let working_set = index(file.path().to_path_buf(), &dialect)?;
+
+        assert_eq!(working_set.values[0], ["Doe, John", "New \"Big\" York"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tab_delimiter_and_custom_quote() -> std::io::Result<()> {
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "name\tnote")?;
+        writeln!(file, "Alice\t'tab\tinside'")?;
+
+        let dialect = Dialect {
+            delimiter: b'\t',
+            quote: b'\'',
+            ..Dialect::default()
+        };
+        let working_set = index(file.path().to_path_buf(), &dialect)?;
+
+        assert_eq!(working_set.values[0], ["Alice", "tab\tinside"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_escape_without_double_quote() -> std::io::Result<()> {
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "id|text")?;
+        writeln!(file, "1|\"say \\\"hi\\\"\"")?;
+
+        let dialect = Dialect {
+            delimiter: b'|',
+            escape: Some(b'\\'),
+            double_quote: false,
+            ..Dialect::default()
+        };
+        let working_set = index(file.path().to_path_buf(), &dialect)?;
+
+        assert_eq!(working_set.values[0], ["1", "say \"hi\""]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_comment_lines_and_trim() -> std::io::Result<()> {
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "name;age")?;
+        writeln!(file, "# exported by some tool")?;
+        writeln!(file, "  Alice ; 25 ")?;
+
+        let dialect = Dialect {
+            comment: Some(b'#'),
+            trim: true,
+            ..Dialect::default()
+        };
+        let working_set = index(file.path().to_path_buf(), &dialect)?;
+
+        assert_eq!(working_set.values.len(), 1);
+        assert_eq!(working_set.values[0], ["Alice", "25"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sniff_comma_with_quotes() {
+        let sample = b"name,city,age\n\"Doe, John\",\"New York\",30\nAlice,LA,25\n";
+        let dialect = sniff_sample(sample, None, None);
+
+        assert_eq!(dialect.delimiter, b',');
+        assert_eq!(dialect.quote, b'"');
+        assert!(dialect.has_headers);
+    }
+
+    #[test]
+    fn test_sniff_picks_most_consistent_delimiter() {
+        // Commas show up in free text, but only semicolons split every record evenly
+        let sample = b"id;note\n1;hello, world\n2;plain\n3;a, b, c\n";
+        assert_eq!(sniff_sample(sample, None, None).delimiter, b';');
+
+        let sample = b"id\tname\n1\tAlice\n2\tBob\n";
+        assert_eq!(sniff_sample(sample, None, None).delimiter, b'\t');
+
+        let sample = b"id|name|age\r\n1|Alice|30\r\n2|Bob|25\r\n";
+        assert_eq!(sniff_sample(sample, None, None).delimiter, b'|');
+    }
+
+    #[test]
+    fn test_sniff_ignores_delimiters_inside_quotes() {
+        let sample = b"a;b\n'x;y;z';1\n'w';2\n";
+        let dialect = sniff_sample(sample, None, None);
+
+        assert_eq!(dialect.quote, b'\'');
+        assert_eq!(dialect.delimiter, b';');
+    }
+
+    #[test]
+    fn test_sniff_detects_missing_header() {
+        let sample = b"1,Alice,30\n2,Bob,25\n3,Charlie,41\n";
+        assert!(!sniff_sample(sample, None, None).has_headers);
+
+        let sample = b"id,name,age\n1,Alice,30\n2,Bob,25\n";
+        assert!(sniff_sample(sample, None, None).has_headers);
+    }
+
+    #[test]
+    fn test_sniff_detects_header_by_length() {
+        let sample = b"code,country\nPL,Poland\nDE,Germany\n";
+        assert!(sniff_sample(sample, None, None).has_headers);
+
+        let sample = b"PL,POL\nDE,DEU\nFR,FRA\n";
+        assert!(!sniff_sample(sample, None, None).has_headers);
+    }
+
+    #[test]
+    fn test_sniff_keeps_given_delimiter_and_quote() {
+        let sample = b"a,b;c\n1,2;3\n";
+        let dialect = sniff_sample(sample, Some(b';'), Some(b'\''));
+
+        assert_eq!(dialect.delimiter, b';');
+        assert_eq!(dialect.quote, b'\'');
+    }
+
+    #[test]
+    fn test_sniff_falls_back_to_default_delimiter() {
+        let sample = b"single\ncolumn\n";
+        assert_eq!(sniff_sample(sample, None, None).delimiter, b';');
+    }
+
+    #[test]
+    fn test_sniff_file_with_truncated_sample() -> std::io::Result<()> {
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "name,age")?;
+        for i in 0..2000 {
+            writeln!(file, "person{},{}", i, i)?;
+        }
+
+        let dialect = sniff(File::open(file.path())?, None, None)?;
+        assert_eq!(dialect.delimiter, b',');
+        assert!(dialect.has_headers);
+
+        let working_set = index(file.path().to_path_buf(), &dialect)?;
+        assert_eq!(working_set.values.len(), 2000);
+        Ok(())
+    }
+
+    fn headerless() -> Dialect {
+        Dialect {
+            has_headers: false,
+            ..Dialect::default()
+        }
+    }
+
+    #[test]
+    fn test_headerless_generates_column_names() -> std::io::Result<()> {
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Alice;25;NYC")?;
+        writeln!(file, "Bob;30;LA")?;
+
+        let set = index_heap_columnar(
+            file.path().to_path_buf(),
+            &headerless(),
+            None,
+            &ColumnTypes::new(),
+            Storage::Memory,
+        )?;
+
+        assert_eq!(set.columns.len(), 3);
+        assert_eq!(set.columns["c0"], 0);
+        assert_eq!(set.columns["c1"], 1);
+        assert_eq!(set.columns["c2"], 2);
+        assert_eq!(set.rows.len(), 2);
+        assert_eq!(set.value(0, 0).unwrap().as_deref(), Some(&b"Alice"[..]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_headerless_with_given_names() -> std::io::Result<()> {
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "Alice;25;NYC")?;
+
+        let names = ["name".to_string(), "age".to_string()];
+        let set = index_heap_columnar(
+            file.path().to_path_buf(),
+            &headerless(),
+            Some(&names),
+            &ColumnTypes::new(),
+            Storage::Memory,
+        )?;
+
+        assert_eq!(set.columns["name"], 0);
+        assert_eq!(set.columns["age"], 1);
+        // Not enough names, remaining one is generated
+        assert_eq!(set.columns["c2"], 2);
+        assert_eq!(set.rows.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_given_names_replace_header() -> std::io::Result<()> {
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "n;a")?;
+        writeln!(file, "Alice;25")?;
+
+        let names = ["name".to_string(), "age".to_string()];
+        let set = index_heap_columnar(
+            file.path().to_path_buf(),
+            &Dialect::default(),
+            Some(&names),
+            &ColumnTypes::new(),
+            Storage::Memory,
+        )?;
+
+        assert_eq!(set.columns.len(), 2);
+        assert_eq!(set.columns["name"], 0);
+        assert_eq!(set.columns["age"], 1);
+        assert_eq!(set.rows.len(), 1);
+        Ok(())
+    }
+
+    fn load_typed(content: &str, schema: &ColumnTypes) -> std::io::Result<ColumnsWorkingSet> {
+        load_with(content, &Dialect::default(), schema)
+    }
+
+    fn load_with(
+        content: &str,
+        dialect: &Dialect,
+        schema: &ColumnTypes,
+    ) -> std::io::Result<ColumnsWorkingSet> {
+        let mut file = NamedTempFile::new()?;
+        write!(file, "{}", content)?;
+        index_heap_columnar(
+            file.path().to_path_buf(),
+            dialect,
+            None,
+            schema,
+            Storage::Memory,
+        )
+    }
+
+    /// Values of column as text, `null` for nulls.
+    fn texts(set: &ColumnsWorkingSet, column: usize) -> Vec<String> {
+        (0..set.rows.len())
+            .map(|row| match set.text(row, column).unwrap() {
+                Some(text) => String::from_utf8(text.into_owned()).unwrap(),
+                None => "null".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_column_types_are_inferred() -> std::io::Result<()> {
+        let set = load_typed(
+            "name;age;score;active;joined;zip\n\
+             Alice;25;1.5;true;2024-01-31;02134\n\
+             Bob;-3;2;false;1999-12-31;10001\n",
+            &ColumnTypes::new(),
+        )?;
+
+        use ColumnType::*;
+        assert_eq!(set.types, [Text, Int, Float, Bool, Date, Text]);
+        // Only text columns keep pointers
+        assert!(set.rows.iter().all(|row| row.len() == 2));
+        assert_eq!(
+            set.value(1, 1).unwrap().unwrap().as_ref(),
+            (-3i64).to_le_bytes()
+        );
+        assert_eq!(texts(&set, 2), ["1.5", "2"]);
+        assert_eq!(texts(&set, 3), ["true", "false"]);
+        assert_eq!(texts(&set, 4), ["2024-01-31", "1999-12-31"]);
+        assert_eq!(texts(&set, 5), ["02134", "10001"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_becomes_text_after_sample() -> std::io::Result<()> {
+        let mut content = "id;name\n".to_string();
+        for i in 0..INFER_SAMPLE_RECORDS + 500 {
+            match i {
+                700 => content.push_str("NA;n700\n"),
+                _ => content.push_str(&format!("{};n{}\n", i, i)),
+            }
+        }
+        content.push_str("n/a;last\n");
+
+        let set = load_typed(&content, &ColumnTypes::new())?;
+
+        assert_eq!(set.types, [ColumnType::Text, ColumnType::Text]);
+        let ids = texts(&set, 0);
+        assert_eq!(ids.len(), INFER_SAMPLE_RECORDS + 501);
+        assert_eq!(ids[1234], "1234");
+        assert_eq!(ids[700], "null");
+        assert_eq!(ids.last().unwrap(), "n/a");
+        assert_eq!(texts(&set, 1)[1234], "n1234");
+        assert!(set.zones.iter().all(Option::is_none));
+        Ok(())
+    }
+
+    #[test]
+    fn test_zones_are_recorded_per_block() -> std::io::Result<()> {
+        let mut content = "id;name;score\n".to_string();
+        for i in 0..3000 {
+            let score = if i == 5 {
+                "NA".to_string()
+            } else {
+                (i % 100).to_string()
+            };
+            content.push_str(&format!("{};n{};{}\n", i, i, score));
+        }
+        let set = load_typed(&content, &ColumnTypes::new())?;
+
+        assert!(set.zones[1].is_none());
+        let ids = set.zones[0].as_ref().unwrap();
+        assert_eq!(ids.block_count(), 3);
+        let int = |value: i64| value.to_le_bytes().to_vec();
+        let block = fixed_rows(8, 1);
+        let (min, max) = ids.zone(1).unwrap();
+        assert_eq!(
+            (min, max),
+            (&int(block.start as i64)[..], &int(block.end as i64 - 1)[..])
+        );
+        assert_eq!(ids.zone(2).unwrap().1, int(2999));
+
+        let scores = set.zones[2].as_ref().unwrap();
+        assert_eq!(scores.zone(0), Some((&int(0)[..], &int(99)[..])));
+        Ok(())
+    }
+
+    #[test]
+    fn test_values_keep_their_text() -> std::io::Result<()> {
+        let set = load_typed(
+            "id;price\n12345678901234567891;10\n12345678901234567892;1.50\n",
+            &ColumnTypes::new(),
+        )?;
+        assert_eq!(set.types, [ColumnType::Text, ColumnType::Text]);
+        assert_eq!(
+            texts(&set, 0),
+            ["12345678901234567891", "12345678901234567892"]
+        );
+        assert_eq!(texts(&set, 1), ["10", "1.50"]);
+
+        // Values after the sample turn the column into text when they would change
+        let mut content = "zip;price\n".to_string();
+        for i in 0..INFER_SAMPLE_RECORDS {
+            content.push_str(&format!("{};{}.5\n", 10000 + i, i));
+        }
+        content.push_str("02134;10.0\n");
+        let set = load_typed(&content, &ColumnTypes::new())?;
+
+        assert_eq!(set.types, [ColumnType::Text, ColumnType::Text]);
+        let (zips, prices) = (texts(&set, 0), texts(&set, 1));
+        assert_eq!(zips[..2], ["10000", "10001"]);
+        assert_eq!(zips[INFER_SAMPLE_RECORDS], "02134");
+        assert_eq!(prices[..2], ["0.5", "1.5"]);
+        assert_eq!(prices[INFER_SAMPLE_RECORDS], "10.0");
+        Ok(())
+    }
+
+    #[test]
+    fn test_schema_overrides_inferred_types() -> std::io::Result<()> {
+        let schema = ColumnTypes::from([
+            ("zip".to_string(), ColumnType::Int),
+            ("age".to_string(), ColumnType::Text),
+            ("missing".to_string(), ColumnType::Date),
+        ]);
+        let set = load_typed("zip;age\n02134;25\n", &schema)?;
+
+        assert_eq!(set.types, [ColumnType::Int, ColumnType::Text]);
+        assert_eq!(texts(&set, 0), ["2134"]);
+
+        let schema = ColumnTypes::from([("age".to_string(), ColumnType::Int)]);
+        let error = load_typed("age\n25\nunknown\n", &schema).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(
+            error.to_string(),
+            "Value \"unknown\" of column age is not int"
+        );
+
+        let set = load_typed("age\n25\nNA\n", &schema)?;
+        assert_eq!(texts(&set, 0), ["25", "null"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_null_values_are_tracked() -> std::io::Result<()> {
+        let set = load_typed(
+            "name;age;joined;note\n\
+             Alice;25;NA;\n\
+             ;\\N;2024-01-31;\n\
+             NULL;;NA;NULL\n",
+            &ColumnTypes::new(),
+        )?;
+
+        use ColumnType::*;
+        // Nulls fit any type, column holding nothing else is text
+        assert_eq!(set.types, [Text, Int, Date, Text]);
+        assert_eq!(texts(&set, 0), ["Alice", "null", "null"]);
+        assert_eq!(texts(&set, 1), ["25", "null", "null"]);
+        assert_eq!(texts(&set, 2), ["null", "2024-01-31", "null"]);
+        assert_eq!(texts(&set, 3), ["null", "null", "null"]);
+        assert_eq!(set.nulls[1].iter().collect::<Vec<_>>(), [1, 2]);
+        assert!(!set.is_null(0, 1));
+        assert!(set.value(2, 1).unwrap().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_null_values() -> std::io::Result<()> {
+        let dialect = Dialect {
+            nulls: vec!["-".to_string()],
+            ..Dialect::default()
+        };
+        let set = load_with("name;age\n;-\nNA;7\n", &dialect, &ColumnTypes::new())?;
+
+        assert_eq!(set.types, [ColumnType::Text, ColumnType::Int]);
+        assert_eq!(texts(&set, 0), ["", "NA"]);
+        assert_eq!(texts(&set, 1), ["null", "7"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_ids_past_u32_are_error() {
+        assert_eq!(row_id(u32::MAX as usize).unwrap(), u32::MAX);
+        let err = row_id(MAX_ROWS as usize).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "File has more than 4294967296 rows");
+    }
+
+    const COUNTRIES: [&str; 4] = ["US", "DE", "", "FR"];
+
+    #[test]
+    fn test_repetitive_column_is_dictionary_encoded() -> std::io::Result<()> {
+        let mut content = "name;country;id\n".to_string();
+        for i in 0..40 {
+            content.push_str(&format!("n{};{};{}\n", i, COUNTRIES[i % 4], i));
+        }
+        let set = load_typed(&content, &ColumnTypes::new())?;
+
+        assert!(set.dictionaries[0].is_none());
+        let dictionary = set.dictionaries[1].as_ref().unwrap();
+        assert_eq!(
+            dictionary.values().collect::<std::io::Result<Vec<_>>>()?,
+            [&b"US"[..], b"DE", b"FR"]
+        );
+        assert!(set.dictionaries[2].is_none(), "only text is encoded");
+        // Only name keeps pointers
+        assert!(set.rows.iter().all(|row| row.len() == 1));
+        assert_eq!(texts(&set, 1)[..5], ["US", "DE", "null", "FR", "US"]);
+        assert_eq!(texts(&set, 0)[39], "n39");
+        assert_eq!(
+            set.stored(4, 1).unwrap().as_deref(),
+            Some(&0u16.to_le_bytes()[..])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_dictionary_column_turns_plain() -> std::io::Result<()> {
+        let mut content = "country;name;city\n".to_string();
+        let count = INFER_SAMPLE_RECORDS * 2;
+        for i in 0..count {
+            // Distinct countries outgrow the dictionary after the sample
+            let country = if i < INFER_SAMPLE_RECORDS {
+                COUNTRIES[i % 4].to_string()
+            } else {
+                format!("c{}", i)
+            };
+            content.push_str(&format!("{};n{};{}\n", country, i, COUNTRIES[i % 3]));
+        }
+        let set = load_typed(&content, &ColumnTypes::new())?;
+
+        assert!(set.dictionaries[0].is_none());
+        assert!(set.dictionaries[2].is_some());
+        assert!(set.rows.iter().all(|row| row.len() == 2));
+        let countries = texts(&set, 0);
+        assert_eq!(countries[..3], ["US", "DE", "null"]);
+        assert_eq!(countries[count - 1], format!("c{}", count - 1));
+        assert_eq!(texts(&set, 1)[1500], "n1500");
+        assert_eq!(texts(&set, 2)[..3], ["US", "DE", "null"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_headerless_index() -> std::io::Result<()> {
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "a;b")?;
+        writeln!(file, "c;d")?;
+
+        let working_set = index(file.path().to_path_buf(), &headerless())?;
+
+        assert!(working_set.columns.contains_key("c0"));
+        assert!(working_set.columns.contains_key("c1"));
+        assert_eq!(working_set.values, [["a", "b"], ["c", "d"]]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_options_detect_or_force_header() -> std::io::Result<()> {
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "1,Alice,30")?;
+        writeln!(file, "2,Bob,25")?;
+
+        let options = LoadOptions::default();
+        let dialect = options.dialect(file.path())?;
+        assert!(!dialect.has_headers);
+        assert_eq!(dialect.nulls, DEFAULT_NULLS);
+
+        let options = LoadOptions {
+            has_headers: Some(true),
+            nulls: Some(vec!["-".to_string()]),
+            ..LoadOptions::default()
+        };
+        let dialect = options.dialect(file.path())?;
+        assert!(dialect.has_headers);
+        assert_eq!(dialect.delimiter, b',');
+        assert_eq!(dialect.nulls, ["-"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_catalog_scans_directory() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir(dir.path().join("sub"))?;
+        std::fs::write(dir.path().join("people.csv"), "name;age\nAlice;25\n")?;
+        std::fs::write(dir.path().join("sub/orders.CSV"), "id,total\n1,10\n2,20\n")?;
+        std::fs::write(dir.path().join("notes.txt"), "not a table\n")?;
+
+        let catalog = load_catalog(dir.path().to_path_buf(), &LoadOptions::default())?;
+
+        assert_eq!(
+            catalog.tables.keys().collect::<Vec<_>>(),
+            ["orders", "people"]
+        );
+        // Dialect is detected per file
+        assert_eq!(catalog.tables["people"].dialect.delimiter, b';');
+        assert_eq!(catalog.tables["orders"].dialect.delimiter, b',');
+        assert_eq!(catalog.table(Some("orders")).unwrap().rows.len(), 2);
+        assert_eq!(catalog.table(Some("people")).unwrap().columns["age"], 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_catalog_with_snapshots() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("people.csv"), "name;age\nAlice;25\n")?;
+        let options = LoadOptions {
+            snapshots: true,
+            ..LoadOptions::default()
+        };
+
+        for _ in 0..2 {
+            let catalog = load_catalog(dir.path().to_path_buf(), &options)?;
+            // Snapshot itself is not a table
+            assert_eq!(catalog.tables.keys().collect::<Vec<_>>(), ["people"]);
+            assert_eq!(catalog.table(None).unwrap().rows.len(), 1);
+            assert!(dir.path().join("people.csv.snapshot").exists());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_catalog_with_schema() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("people.csv"), "name;zip\nAlice;02134\n")?;
+        std::fs::write(dir.path().join("orders.csv"), "id;zip\n1;02134\n")?;
+        let options = LoadOptions {
+            schema: Schema::parse("zip: int\norders.id: float")?,
+            ..LoadOptions::default()
+        };
+
+        let catalog = load_catalog(dir.path().to_path_buf(), &options)?;
+
+        use ColumnType::*;
+        assert_eq!(catalog.table(Some("people")).unwrap().types, [Text, Int]);
+        assert_eq!(catalog.table(Some("orders")).unwrap().types, [Float, Int]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_catalog_single_file() -> std::io::Result<()> {
+        let mut file = NamedTempFile::with_suffix(".csv")?;
+        writeln!(file, "name;age")?;
+        writeln!(file, "Alice;25")?;
+
+        let catalog = load_catalog(file.path().to_path_buf(), &LoadOptions::default())?;
+
+        assert_eq!(catalog.tables.len(), 1);
+        // Single table is used when none is selected
+        assert_eq!(catalog.table(None).unwrap().rows.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_catalog_skips_duplicate_table_name() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir(dir.path().join("sub"))?;
+        std::fs::write(dir.path().join("data.csv"), "a\n1\n")?;
+        std::fs::write(dir.path().join("sub/data.csv"), "a\n2\n")?;
+        std::fs::write(dir.path().join("sub/other.csv"), "b\n3\n")?;
+
+        let catalog = load_catalog(dir.path().to_path_buf(), &LoadOptions::default())?;
+
+        assert_eq!(catalog.tables.keys().collect::<Vec<_>>(), ["data", "other"]);
+        assert_eq!(catalog.tables["data"].path, dir.path().join("data.csv"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_catalog_skips_malformed_files() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("good.csv"), "a,b\n1,2\n3,4\n")?;
+        std::fs::write(dir.path().join("bad.csv"), "a,b\n1,2\n3\n")?;
+
+        let catalog = load_catalog(dir.path().to_path_buf(), &LoadOptions::default())?;
+        assert_eq!(catalog.tables.keys().collect::<Vec<_>>(), ["good"]);
+        assert_eq!(catalog.tables["good"].set.rows.len(), 2);
+
+        // File given directly still fails
+        let options = LoadOptions::default();
+        assert!(load_catalog(dir.path().join("bad.csv"), &options).is_err());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_load_catalog_skips_symlinked_directories() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir(dir.path().join("sub"))?;
+        std::fs::write(dir.path().join("sub/people.csv"), "name\nAlice\n")?;
+        // Link back up the tree would recurse forever
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("sub/loop"))?;
+        std::os::unix::fs::symlink(
+            dir.path().join("sub/people.csv"),
+            dir.path().join("linked.csv"),
+        )?;
+
+        let catalog = load_catalog(dir.path().to_path_buf(), &LoadOptions::default())?;
+
+        assert_eq!(
+            catalog.tables.keys().collect::<Vec<_>>(),
+            ["linked", "people"]
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_load_catalog_skips_unreadable_directories() -> std::io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir()?;
+        let locked = dir.path().join("locked");
+        std::fs::create_dir(&locked)?;
+        std::fs::write(locked.join("secret.csv"), "a\n1\n")?;
+        std::fs::write(dir.path().join("people.csv"), "name\nAlice\n")?;
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000))?;
+
+        let catalog = load_catalog(dir.path().to_path_buf(), &LoadOptions::default());
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755))?;
+
+        // Root ignores permissions, then the directory is simply read
+        let catalog = catalog?;
+        assert!(catalog.tables.contains_key("people"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_catalog_requires_table_name() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("a.csv"), "x\n1\n")?;
+        std::fs::write(dir.path().join("b.csv"), "x\n2\n")?;
+
+        let catalog = load_catalog(dir.path().to_path_buf(), &LoadOptions::default())?;
+        let error = |name| catalog.table(name).err().unwrap().to_string();
+        assert_eq!(
+            error(None),
+            "Execution Error: Table must be selected with @ when more than one is loaded"
+        );
+        assert_eq!(error(Some("c")), "Execution Error: Missing table c");
+        assert!(catalog.table(Some("a")).is_ok());
+        Ok(())
+    }
+
+    const PEOPLE: &[u8] = b"name;age\nAlice;25\nBob;30\n";
+
+    #[test]
+    fn test_load_compressed_files() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(PEOPLE)?;
+        std::fs::write(dir.path().join("gzipped.csv.gz"), gz.finish()?)?;
+
+        std::fs::write(
+            dir.path().join("zstd.CSV.zst"),
+            zstd::encode_all(PEOPLE, 0)?,
+        )?;
+
+        let mut bz = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        bz.write_all(PEOPLE)?;
+        std::fs::write(dir.path().join("bzipped.csv.bz2"), bz.finish()?)?;
+
+        let catalog = load_catalog(dir.path().to_path_buf(), &LoadOptions::default())?;
+
+        assert_eq!(
+            catalog.tables.keys().collect::<Vec<_>>(),
+            ["bzipped", "gzipped", "zstd"]
+        );
+        for table in catalog.tables.values() {
+            assert_eq!(table.dialect.delimiter, b';');
+            assert_eq!(table.set.rows.len(), 2);
+            assert_eq!(table.set.value(1, 0).unwrap().as_deref(), Some(&b"Bob"[..]));
+        }
+        Ok(())
+    }
+
+    fn write_zip(path: &Path) -> std::io::Result<()> {
+        let mut zip = zip::ZipWriter::new(File::create(path)?);
+        let options = zip::write::SimpleFileOptions::default();
+        zip.add_directory("data/", options)?;
+        zip.start_file("data/people.csv", options)?;
+        zip.write_all(PEOPLE)?;
+        zip.start_file("readme.txt", options)?;
+        zip.write_all(b"not a table")?;
+        zip.finish()?;
+        Ok(())
+    }
+
+    fn write_tar_gz(path: &Path) -> std::io::Result<()> {
+        let gz = flate2::write::GzEncoder::new(File::create(path)?, flate2::Compression::default());
+        let mut tar = tar::Builder::new(gz);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(PEOPLE.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append_data(&mut header, "orders.csv", PEOPLE)?;
+        tar.into_inner()?.finish()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_archives_when_included() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        write_zip(&dir.path().join("drop.zip"))?;
+        write_tar_gz(&dir.path().join("drop.tar.gz"))?;
+        std::fs::write(dir.path().join("plain.csv"), PEOPLE)?;
+
+        let catalog = load_catalog(dir.path().to_path_buf(), &LoadOptions::default())?;
+        assert_eq!(catalog.tables.keys().collect::<Vec<_>>(), ["plain"]);
+
+        let options = LoadOptions {
+            include_archives: true,
+            ..LoadOptions::default()
+        };
+        let catalog = load_catalog(dir.path().to_path_buf(), &options)?;
+
+        assert_eq!(
+            catalog.tables.keys().collect::<Vec<_>>(),
+            ["orders", "people", "plain"]
+        );
+        assert_eq!(
+            catalog.tables["people"].path,
+            dir.path().join("drop.zip/data/people.csv")
+        );
+        let orders = catalog.table(Some("orders")).unwrap();
+        assert_eq!(orders.rows.len(), 2);
+        assert_eq!(orders.text(0, 1).unwrap().as_deref(), Some(&b"25"[..]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_archive_given_directly() -> std::io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("drop.zip");
+        write_zip(&path)?;
+
+        let catalog = load_catalog(path, &LoadOptions::default())?;
+
+        assert_eq!(catalog.table(None).unwrap().rows.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_name_helpers() {
+        assert_eq!(csv_stem("orders.csv"), Some("orders"));
+        assert_eq!(csv_stem("dir/orders.CSV.gz"), Some("orders"));
+        assert_eq!(csv_stem("orders.csv.zst"), Some("orders"));
+        assert_eq!(csv_stem("orders.txt"), None);
+        assert_eq!(csv_stem(".csv"), None);
+
+        assert_eq!(Archive::of("drop.zip"), Some(Archive::Zip));
+        assert_eq!(
+            Archive::of("drop.tar"),
+            Some(Archive::Tar(Compression::None))
+        );
+        assert_eq!(
+            Archive::of("drop.TAR.GZ"),
+            Some(Archive::Tar(Compression::Gzip))
+        );
+        assert_eq!(
+            Archive::of("drop.tgz"),
+            Some(Archive::Tar(Compression::Gzip))
+        );
+        assert_eq!(Archive::of("orders.csv.gz"), None);
+    }
+
+    #[test]
+    fn test_parse_dialect_char() {
+        assert_eq!(parse_dialect_char(","), Ok(b','));
+        assert_eq!(parse_dialect_char("tab"), Ok(b'\t'));
+        assert_eq!(parse_dialect_char("\\t"), Ok(b'\t'));
+        assert_eq!(parse_dialect_char("pipe"), Ok(b'|'));
+        assert!(parse_dialect_char(",,").is_err());
+        assert!(parse_dialect_char("").is_err());
+        assert!(parse_dialect_char("ł").is_err());
+    }
+
+    #[test]
     fn test_nonexistent_file() {
-        let result = index(PathBuf::from("/nonexistent/file.csv"));
+        let result = load_catalog(
+            PathBuf::from("/nonexistent/file.csv"),
+            &LoadOptions::default(),
+        );
 
-        assert!(result.is_err()); // Should return IO error
+        assert!(result.is_err_and(|err| err.kind() == std::io::ErrorKind::NotFound));
     }
 }
@@ -1,14 +1,12 @@
 use std::rc::Rc;
 
 use crate::{
+    aggregate::AggregateFunc,
+    index::IndexKind,
     query::scanner::Scanner,
     query::token::{Token, TokenType},
 };
 
-/// Return values from Statement.
-/// (Coulumns, Values)
-type ResultSet = (Vec<usize>, Vec<usize>);
-
 type ParserResult<T> = Result<T, ParserError>;
 
 impl std::error::Error for ParserError {}
@@ -20,7 +18,7 @@ impl std::fmt::Display for ParserError {
 }
 
 #[derive(Debug)]
-pub(crate) struct ParserError {
+pub struct ParserError {
     pub(crate) position: usize,
     pub(crate) msg: String,
 }
@@ -34,17 +32,15 @@ impl ParserError {
     }
 }
 
-pub(crate) struct CmdParser {
+#[derive(Default)]
+pub struct CmdParser {
     tokens: Vec<Rc<Token>>,
     current: usize,
 }
 
 impl CmdParser {
-    pub(crate) fn new() -> CmdParser {
-        Self {
-            tokens: vec![],
-            current: 0,
-        }
+    pub fn new() -> CmdParser {
+        Self::default()
     }
 
     fn peek_expect(&self, token_type: TokenType) -> ParserResult<bool> {
@@ -52,7 +48,7 @@ impl CmdParser {
     }
 
     // TODO: Make it Result<>
-    pub(crate) fn parse_string(mut self, command: &str) -> ParserResult<Statement> {
+    pub fn parse_string(mut self, command: &str) -> ParserResult<Statement> {
         //(|set| {})
         let mut scanner = Scanner::new(command);
 
@@ -67,13 +63,32 @@ impl CmdParser {
 
     fn statement(&mut self) -> ParserResult<Statement> {
         let token = self.consume()?;
-        match token.t {
+        let statement = match token.t {
             TokenType::Get => self.get_statement(),
+            TokenType::Index => self.index_statement(),
+            TokenType::Create => {
+                self.consume_if(|t| matches!(t, TokenType::Index))?;
+                self.consume_if(|t| matches!(t, TokenType::On))?;
+                self.index_statement()
+            }
             _ => Err(ParserError::new(
                 self.current,
                 "Parser Error: Expected unexpected operation",
             )),
+        }?;
+
+        if !self.finished() {
+            let token = self.current()?;
+            return Err(ParserError::new(
+                token.position,
+                &format!(
+                    "Parser Error: Unexpected token {:?}",
+                    token.literal.to_string()
+                ),
+            ));
         }
+
+        Ok(statement)
     }
 
     fn get_statement(&mut self) -> ParserResult<Statement> {
@@ -91,30 +106,166 @@ impl CmdParser {
             condition = Some(self.conditional()?);
         }
 
-        Ok(Statement::Get(columns, tables, condition))
+        let mut clauses = Clauses::default();
+        if self.peek_expect(TokenType::Group)? {
+            self.consume()?;
+            self.consume_if(|t| matches!(t, TokenType::By))?;
+            clauses.group_by = Some(self.terms()?);
+        }
+
+        if self.peek_expect(TokenType::Having)? {
+            self.consume()?;
+            clauses.having = Some(self.conditional()?);
+        }
+
+        if self.peek_expect(TokenType::Order)? {
+            self.consume()?;
+            self.consume_if(|t| matches!(t, TokenType::By))?;
+            clauses.order_by = self.order_keys()?;
+        }
+
+        if self.peek_expect(TokenType::Limit)? {
+            self.consume()?;
+            clauses.limit = Some(self.count()?);
+
+            if self.peek_expect(TokenType::Offset)? {
+                self.consume()?;
+                clauses.offset = self.count()?;
+            }
+        }
+
+        Ok(Statement::Get(columns, tables, condition, clauses))
+    }
+
+    fn index_statement(&mut self) -> ParserResult<Statement> {
+        let columns = self.terms()?;
+        let mut kind = IndexKind::BTree;
+        let mut table = None;
+
+        if self.peek_expect(TokenType::Using)? {
+            self.consume()?;
+            let name = self.consume()?;
+            kind = IndexKind::from_name(&name.literal.to_string()).ok_or_else(|| {
+                ParserError::new(
+                    name.position,
+                    &format!(
+                        "Parser Error: Unknown index type {:?}",
+                        name.literal.to_string()
+                    ),
+                )
+            })?;
+            if kind != IndexKind::BTree && matches!(columns, Expr::Multiple(..)) {
+                return Err(ParserError::new(
+                    name.position,
+                    &format!("Parser Error: {} index can't span several columns", kind),
+                ));
+            }
+        }
+
+        if self.peek_expect(TokenType::At)? {
+            self.consume()?;
+            table = Some(self.term()?);
+        }
+
+        Ok(Statement::Index(columns, kind, table))
     }
 
+    /// conditional := and ("or" and)*
     fn conditional(&mut self) -> ParserResult<Expr> {
-        let mut left = self.comparison()?;
+        let mut left = self.and()?;
+
+        while self.peek_expect(TokenType::Or)? {
+            let operator = self.consume()?;
+            let right = self.and()?;
+            left = Expr::Conditional(Box::new(left), operator, Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    /// and := unary ("and" unary)*
+    fn and(&mut self) -> ParserResult<Expr> {
+        let mut left = self.unary()?;
 
-        while !self.finished() && matches!(self.current()?.t, TokenType::And | TokenType::Or) {
+        while self.peek_expect(TokenType::And)? {
             let operator = self.consume()?;
-            let right = self.comparison()?;
+            let right = self.unary()?;
             left = Expr::Conditional(Box::new(left), operator, Box::new(right));
         }
 
         Ok(left)
     }
 
+    /// unary := ("not" | "!") unary | primary
+    fn unary(&mut self) -> ParserResult<Expr> {
+        if self.peek_expect(TokenType::Bang)? {
+            let operator = self.consume()?;
+            let right = self.unary()?;
+            return Ok(Expr::Unary(operator, Box::new(right)));
+        }
+
+        self.primary()
+    }
+
+    /// primary := "(" conditional ")" | comparison
+    fn primary(&mut self) -> ParserResult<Expr> {
+        if self.peek_expect(TokenType::LeftParen)? {
+            self.consume()?;
+            let expr = self.conditional()?;
+            self.consume_if(|t| matches!(t, TokenType::RightParen))?;
+            return Ok(expr);
+        }
+
+        self.comparison()
+    }
+
+    /// comparison := column operator term | column "is" "not"? "null"
     fn comparison(&mut self) -> ParserResult<Expr> {
-        let left = self.term()?;
-        let operator = self.consume_if(|t| matches!(t, TokenType::Equals))?;
+        let left = self.column()?;
+        if self.peek_expect(TokenType::Is)? {
+            self.consume()?;
+            let negated = self.peek_expect(TokenType::Bang)?;
+            if negated {
+                self.consume()?;
+            }
+            self.consume_if(|t| matches!(t, TokenType::Null))?;
+            return Ok(Expr::IsNull(Box::new(left), negated));
+        }
+
+        let operator = self.consume_if(|t| {
+            matches!(
+                t,
+                TokenType::Equals
+                    | TokenType::NotEquals
+                    | TokenType::Less
+                    | TokenType::LessEquals
+                    | TokenType::Greater
+                    | TokenType::GreaterEquals
+            )
+        })?;
         let right = self.term()?;
 
         Ok(Expr::Conditional(Box::new(left), operator, Box::new(right)))
     }
 
     fn multiple(&mut self) -> ParserResult<Expr> {
+        let mut left = self.column()?;
+
+        while !self.finished()
+            && matches!(
+                self.current()?.t,
+                TokenType::Identifier | TokenType::QuotedValue
+            )
+        {
+            let right = self.column()?;
+            left = Expr::Multiple(Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    /// terms := term term*
+    fn terms(&mut self) -> ParserResult<Expr> {
         let mut left = self.term()?;
 
         while !self.finished()
@@ -130,6 +281,83 @@ impl CmdParser {
         Ok(left)
     }
 
+    /// order_keys := column ("asc" | "desc")? (column ("asc" | "desc")?)*
+    fn order_keys(&mut self) -> ParserResult<Vec<OrderKey>> {
+        let mut keys = vec![];
+
+        loop {
+            let expr = self.column()?;
+            let mut descending = false;
+            if self.peek_expect(TokenType::Asc)? {
+                self.consume()?;
+            } else if self.peek_expect(TokenType::Desc)? {
+                self.consume()?;
+                descending = true;
+            }
+            keys.push(OrderKey { expr, descending });
+
+            if self.finished()
+                || !matches!(
+                    self.current()?.t,
+                    TokenType::Identifier | TokenType::QuotedValue
+                )
+            {
+                return Ok(keys);
+            }
+        }
+    }
+
+    /// Non negative integer, e.g. limit or offset value.
+    fn count(&mut self) -> ParserResult<usize> {
+        let token =
+            self.consume_if(|t| matches!(t, TokenType::Identifier | TokenType::QuotedValue))?;
+        token.literal.to_string().parse::<usize>().map_err(|_| {
+            ParserError::new(
+                token.position,
+                &format!(
+                    "Parser Error: Expected non negative number, got {:?}",
+                    token.literal.to_string()
+                ),
+            )
+        })
+    }
+
+    /// column := term | aggregate "(" (term | "*") ")"
+    fn column(&mut self) -> ParserResult<Expr> {
+        let term = self.term()?;
+        if !self.peek_expect(TokenType::LeftParen)? {
+            return Ok(term);
+        }
+
+        let Expr::Literal(function) = term else {
+            unreachable!("term always returns literal");
+        };
+        let name = function.literal.to_string();
+        let Some(aggregate) = AggregateFunc::from_name(&name) else {
+            return Err(ParserError::new(
+                function.position,
+                &format!("Parser Error: Unknown function {:?}", name),
+            ));
+        };
+
+        self.consume()?;
+        let argument = if self.peek_expect(TokenType::Star)? {
+            let star = self.consume()?;
+            if aggregate != AggregateFunc::Count {
+                return Err(ParserError::new(
+                    star.position,
+                    "Parser Error: Only count accepts *",
+                ));
+            }
+            Expr::Literal(star)
+        } else {
+            self.term()?
+        };
+        self.consume_if(|t| matches!(t, TokenType::RightParen))?;
+
+        Ok(Expr::Call(function, Box::new(argument)))
+    }
+
     fn term(&mut self) -> ParserResult<Expr> {
         let current = self.consume()?;
 
@@ -196,16 +424,44 @@ impl CmdParser {
 
 // TODO: Add it later: #[derive(Debug)]
 #[derive(Debug)]
-pub(crate) enum Expr {
+pub enum Expr {
     Literal(Rc<Token>),
     Multiple(Box<Expr>, Box<Expr>),
     Conditional(Box<Expr>, Rc<Token>, Box<Expr>),
+    Unary(Rc<Token>, Box<Expr>),
+    /// `column is null`, or `column is not null` when negated
+    IsNull(Box<Expr>, bool),
+    /// Aggregate function applied to a column or `*`
+    Call(Rc<Token>, Box<Expr>),
 }
 
 #[derive(Debug)]
-pub(crate) enum Statement {
-    /// "get" token ("," + token)* "@" token "where" conditional_expr
-    Get(Expr, Option<Expr>, Option<Expr>),
+pub enum Statement {
+    /// "get" token ("," + token)* "@" token "where" conditional_expr clauses
+    Get(Expr, Option<Expr>, Option<Expr>, Clauses),
+    /// ("index" | "create" "index" "on") token+ ("using" kind)? ("@" token)?
+    Index(Expr, IndexKind, Option<Expr>),
+}
+
+/// Optional clauses that follow the where condition.
+#[derive(Debug, Default)]
+pub struct Clauses {
+    /// "group by" token+
+    pub(crate) group_by: Option<Expr>,
+    /// "having" conditional_expr
+    pub(crate) having: Option<Expr>,
+    /// "order by" (column ("asc" | "desc")?)+
+    pub(crate) order_by: Vec<OrderKey>,
+    /// "limit" number
+    pub(crate) limit: Option<usize>,
+    /// "offset" number, only allowed after limit
+    pub(crate) offset: usize,
+}
+
+#[derive(Debug)]
+pub struct OrderKey {
+    pub(crate) expr: Expr,
+    pub(crate) descending: bool,
 }
 
 impl Statement {
@@ -214,13 +470,14 @@ impl Statement {
     }
 }
 
-pub(crate) trait Visitor<R: Sized> {
+pub trait Visitor<R: Sized> {
     fn visit(&self, expr: &Statement) -> R;
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::query::parser::{CmdParser, Expr, Statement};
+    use crate::index::IndexKind;
+    use crate::query::parser::{Clauses, CmdParser, Expr, Statement};
 
     fn extract_columns(expr: &Expr) -> Vec<String> {
         match expr {
@@ -240,7 +497,7 @@ mod tests {
         let statement = p.parse_string("get name").unwrap();
 
         match statement {
-            Statement::Get(expr, None, None) => {
+            Statement::Get(expr, None, None, _) => {
                 let cols = extract_columns(&expr);
                 assert_eq!(cols, vec!["name"]);
             }
@@ -254,7 +511,7 @@ mod tests {
         let statement = p.parse_string("get name age city").unwrap();
 
         match statement {
-            Statement::Get(expr, None, None) => {
+            Statement::Get(expr, None, None, _) => {
                 let cols = extract_columns(&expr);
                 assert_eq!(cols, vec!["name", "age", "city"]);
             }
@@ -268,7 +525,7 @@ mod tests {
         let statement = p.parse_string("get a b").unwrap();
 
         match statement {
-            Statement::Get(Expr::Multiple(left, right), None, None) => {
+            Statement::Get(Expr::Multiple(left, right), None, None, _) => {
                 assert!(matches!(left.as_ref(), Expr::Literal(_)));
                 assert!(matches!(right.as_ref(), Expr::Literal(_)));
             }
@@ -289,7 +546,7 @@ mod tests {
         let statement = p.parse_string("get name @ users").unwrap();
 
         match statement {
-            Statement::Get(cols, Some(table), None) => {
+            Statement::Get(cols, Some(table), None, _) => {
                 assert_eq!(extract_columns(&cols), vec!["name"]);
                 assert_eq!(extract_table(&table), "users");
             }
@@ -303,7 +560,7 @@ mod tests {
         let statement = p.parse_string("get name @ \"users\"").unwrap();
 
         match statement {
-            Statement::Get(cols, Some(table), None) => {
+            Statement::Get(cols, Some(table), None, _) => {
                 assert_eq!(extract_columns(&cols), vec!["name"]);
                 assert_eq!(extract_table(&table), "users");
             }
@@ -333,7 +590,7 @@ mod tests {
             .unwrap();
 
         match statement {
-            Statement::Get(cols, Some(table), Some(condition)) => {
+            Statement::Get(cols, Some(table), Some(condition), _) => {
                 assert_eq!(extract_columns(&cols), vec!["name"]);
                 assert_eq!(extract_table(&table), "users");
 
@@ -349,4 +606,358 @@ mod tests {
             _ => unreachable!(),
         }
     }
+
+    #[test]
+    fn test_where_with_comparison_operators() {
+        for op in ["=", "!=", "<", "<=", ">", ">="] {
+            let p = CmdParser::new();
+            let statement = p
+                .parse_string(&format!(r#"get name where age {} "30""#, op))
+                .unwrap();
+
+            match statement {
+                Statement::Get(_, None, Some(Expr::Conditional(left, operator, right)), _) => {
+                    assert_eq!(extract_table(&left), "age");
+                    assert_eq!(operator.literal.to_string(), op);
+                    assert_eq!(extract_table(&right), "30");
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    /// Renders condition tree with explicit parentheses, so precedence is easy to assert.
+    fn render(expr: &Expr) -> String {
+        match expr {
+            Expr::Literal(token) => token.literal.to_string(),
+            Expr::Conditional(left, op, right) => {
+                format!("({} {} {})", render(left), op.literal, render(right))
+            }
+            Expr::Unary(op, right) => format!("({} {})", op.literal, render(right)),
+            Expr::IsNull(column, false) => format!("({} is null)", render(column)),
+            Expr::IsNull(column, true) => format!("({} is not null)", render(column)),
+            _ => unreachable!(),
+        }
+    }
+
+    fn parse_condition(query: &str) -> String {
+        match CmdParser::new().parse_string(query).unwrap() {
+            Statement::Get(_, _, Some(condition), _) => render(&condition),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_and_binds_tighter_than_or() {
+        assert_eq!(
+            parse_condition(r#"get x where a = "1" or b = "2" and c = "3""#),
+            "((a = 1) or ((b = 2) and (c = 3)))"
+        );
+        assert_eq!(
+            parse_condition(r#"get x where a = "1" and b = "2" or c = "3""#),
+            "(((a = 1) and (b = 2)) or (c = 3))"
+        );
+    }
+
+    #[test]
+    fn test_parentheses_override_precedence() {
+        assert_eq!(
+            parse_condition(r#"get x where (a = "1" or b = "2") and c = "3""#),
+            "(((a = 1) or (b = 2)) and (c = 3))"
+        );
+        assert_eq!(parse_condition(r#"get x where ((a = "1"))"#), "(a = 1)");
+    }
+
+    #[test]
+    fn test_not_and_bang() {
+        assert_eq!(
+            parse_condition(r#"get x where not a = "1" and b = "2""#),
+            "((not (a = 1)) and (b = 2))"
+        );
+        assert_eq!(
+            parse_condition(r#"get x where !(a = "1" or b = "2")"#),
+            "(! ((a = 1) or (b = 2)))"
+        );
+    }
+
+    #[test]
+    fn test_is_null() {
+        assert_eq!(
+            parse_condition(r#"get x where a is null or b IS NOT NULL"#),
+            "((a is null) or (b is not null))"
+        );
+        assert_eq!(
+            parse_condition(r#"get x where not a is null and b = "2""#),
+            "((not (a is null)) and (b = 2))"
+        );
+    }
+
+    #[test]
+    fn test_invalid_is_null_returns_error() {
+        let p = CmdParser::new();
+        assert!(p.parse_string("get x where a is").is_err());
+
+        let p = CmdParser::new();
+        assert!(p.parse_string(r#"get x where a is "1""#).is_err());
+
+        let p = CmdParser::new();
+        assert!(p.parse_string("get x where a = null").is_err());
+    }
+
+    #[test]
+    fn test_unbalanced_parentheses_return_error() {
+        let p = CmdParser::new();
+        assert!(p.parse_string(r#"get x where (a = "1""#).is_err());
+
+        let p = CmdParser::new();
+        assert!(p.parse_string(r#"get x where a = "1")"#).is_err());
+    }
+
+    #[test]
+    fn test_get_aggregates() {
+        let p = CmdParser::new();
+        let statement = p
+            .parse_string(r#"get count(*) sum(price) "avg"(age) name"#)
+            .unwrap();
+
+        let Statement::Get(expr, None, None, _) = statement else {
+            unreachable!();
+        };
+
+        let mut calls = vec![];
+        let mut current = &expr;
+        while let Expr::Multiple(left, right) = current {
+            calls.push(right.as_ref());
+            current = left;
+        }
+        calls.push(current);
+        calls.reverse();
+
+        let rendered: Vec<String> = calls
+            .iter()
+            .map(|e| match e {
+                Expr::Call(function, argument) => {
+                    format!("{}({})", function.literal, extract_table(argument))
+                }
+                Expr::Literal(token) => token.literal.to_string(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(rendered, ["count(*)", "sum(price)", "avg(age)", "name"]);
+    }
+
+    #[test]
+    fn test_group_by_and_having() {
+        let p = CmdParser::new();
+        let statement = p
+            .parse_string(
+                r#"get city count(*) where age > "20" group by city country having count(*) > "5""#,
+            )
+            .unwrap();
+
+        match statement {
+            Statement::Get(
+                _,
+                None,
+                Some(_),
+                Clauses {
+                    group_by: Some(group_by),
+                    having: Some(having),
+                    ..
+                },
+            ) => {
+                assert_eq!(extract_columns(&group_by), vec!["city", "country"]);
+                match having {
+                    Expr::Conditional(left, op, right) => {
+                        assert!(matches!(left.as_ref(), Expr::Call(..)));
+                        assert_eq!(op.literal.to_string(), ">");
+                        assert_eq!(extract_table(&right), "5");
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_order_by() {
+        let p = CmdParser::new();
+        let statement = p
+            .parse_string(r#"get city count(*) group by city order by count(*) desc city asc age"#)
+            .unwrap();
+
+        let Statement::Get(_, None, None, clauses) = statement else {
+            unreachable!();
+        };
+        let keys: Vec<(String, bool)> = clauses
+            .order_by
+            .iter()
+            .map(|key| match &key.expr {
+                Expr::Call(function, argument) => (
+                    format!("{}({})", function.literal, extract_table(argument)),
+                    key.descending,
+                ),
+                expr => (extract_table(expr), key.descending),
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                ("count(*)".to_string(), true),
+                ("city".to_string(), false),
+                ("age".to_string(), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_order_without_keys_returns_error() {
+        let p = CmdParser::new();
+        assert!(p.parse_string("get city order by").is_err());
+
+        let p = CmdParser::new();
+        assert!(p.parse_string("get city order by desc").is_err());
+    }
+
+    #[test]
+    fn test_limit_and_offset() {
+        let p = CmdParser::new();
+        let statement = p
+            .parse_string("get name order by age desc limit 10 offset 20")
+            .unwrap();
+
+        let Statement::Get(_, None, None, clauses) = statement else {
+            unreachable!();
+        };
+        assert_eq!(clauses.order_by.len(), 1);
+        assert_eq!(clauses.limit, Some(10));
+        assert_eq!(clauses.offset, 20);
+
+        let p = CmdParser::new();
+        let statement = p.parse_string(r#"get name limit "5""#).unwrap();
+        let Statement::Get(_, None, None, clauses) = statement else {
+            unreachable!();
+        };
+        assert_eq!(clauses.limit, Some(5));
+        assert_eq!(clauses.offset, 0);
+    }
+
+    #[test]
+    fn test_invalid_limit_returns_error() {
+        for query in [
+            "get name limit",
+            "get name limit ten",
+            "get name limit -1",
+            "get name limit 5 offset",
+            "get name offset 5",
+        ] {
+            let p = CmdParser::new();
+            assert!(p.parse_string(query).is_err(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_group_without_by_returns_error() {
+        let p = CmdParser::new();
+        assert!(p.parse_string("get city group city").is_err());
+
+        let p = CmdParser::new();
+        assert!(p.parse_string("get city group by").is_err());
+    }
+
+    #[test]
+    fn test_unknown_function_returns_error() {
+        let p = CmdParser::new();
+        assert!(p.parse_string("get median(price)").is_err());
+    }
+
+    #[test]
+    fn test_star_outside_count_returns_error() {
+        let p = CmdParser::new();
+        assert!(p.parse_string("get sum(*)").is_err());
+
+        let p = CmdParser::new();
+        assert!(p.parse_string("get count(*").is_err());
+    }
+
+    #[test]
+    fn test_where_with_invalid_operator_returns_error() {
+        let p = CmdParser::new();
+        let result = p.parse_string(r#"get name where age @ "30""#);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_index_statement() {
+        for input in ["index age", "create index on age", "CREATE INDEX ON age"] {
+            let p = CmdParser::new();
+            match p.parse_string(input).unwrap() {
+                Statement::Index(column, IndexKind::BTree, None) => {
+                    assert_eq!(extract_table(&column), "age")
+                }
+                other => panic!("Expected index statement, got {:?}", other),
+            }
+        }
+
+        let p = CmdParser::new();
+        match p.parse_string(r#"index "first name" @ users"#).unwrap() {
+            Statement::Index(column, IndexKind::BTree, Some(table)) => {
+                assert_eq!(extract_table(&column), "first name");
+                assert_eq!(extract_table(&table), "users");
+            }
+            other => panic!("Expected index statement, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_invalid_index_statement_returns_error() {
+        for input in [
+            "index",
+            "create age",
+            "create index age",
+            "index age using",
+            "index first last using hash",
+            "index age using trie",
+        ] {
+            let p = CmdParser::new();
+            assert!(p.parse_string(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_composite_index_statement() {
+        let p = CmdParser::new();
+        match p
+            .parse_string(r#"create index on first "last name" @ users"#)
+            .unwrap()
+        {
+            Statement::Index(columns, IndexKind::BTree, Some(table)) => {
+                assert_eq!(extract_columns(&columns), ["first", "last name"]);
+                assert_eq!(extract_table(&table), "users");
+            }
+            other => panic!("Expected composite index statement, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_index_statement_with_kind() {
+        let p = CmdParser::new();
+        match p
+            .parse_string("create index on id using HASH @ users")
+            .unwrap()
+        {
+            Statement::Index(column, IndexKind::Hash, Some(table)) => {
+                assert_eq!(extract_table(&column), "id");
+                assert_eq!(extract_table(&table), "users");
+            }
+            other => panic!("Expected hash index statement, got {:?}", other),
+        }
+
+        let p = CmdParser::new();
+        assert!(matches!(
+            p.parse_string("index age using btree").unwrap(),
+            Statement::Index(_, IndexKind::BTree, None)
+        ));
+    }
 }
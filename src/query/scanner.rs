@@ -24,8 +24,6 @@ pub(crate) struct Scanner<'a> {
     state: State,
 }
 
-enum Statement {}
-
 impl<'a> Scanner<'a> {
     pub fn new(input_string: &'a str) -> Self {
         let len = input_string.len();
@@ -51,9 +49,15 @@ impl<'a> Scanner<'a> {
                 (Some(b), State::StartCmd) if b.is_ascii_whitespace() => {
                     self.advance();
                 }
-                (Some(b), State::StartCmd) if matches!(b, b'!' | b'=' | b'@') => {
+                (Some(b), State::StartCmd) if is_operator(b) => {
                     value.push(b);
                     self.advance();
+
+                    // Two character operators: !=, <=, >=
+                    if matches!(b, b'!' | b'<' | b'>') && self.peek() == Some(b'=') {
+                        value.push(b'=');
+                        self.advance();
+                    }
                     self.state = State::EndTerm
                 }
                 (Some(b), State::StartCmd) if b == b"\""[0] => {
@@ -61,7 +65,7 @@ impl<'a> Scanner<'a> {
                     self.start = self.position;
                     self.advance();
                 }
-                (Some(b), State::InTerm) if b.is_ascii_whitespace() => {
+                (Some(b), State::InTerm) if b.is_ascii_whitespace() || is_operator(b) => {
                     self.state = State::EndTerm;
                 }
                 (Some(_), State::StartCmd) => {
@@ -127,6 +131,11 @@ impl<'a> Scanner<'a> {
     }
 }
 
+#[inline]
+fn is_operator(b: u8) -> bool {
+    matches!(b, b'!' | b'=' | b'@' | b'<' | b'>' | b'(' | b')' | b'*')
+}
+
 #[cfg(test)]
 mod tests {
     use crate::query::{
@@ -594,15 +603,121 @@ mod tests {
         assert_eq!(t.t, TokenType::Identifier);
         assert_eq!(t.literal, TokenLiteral::Str("name".to_string()));
 
+        let t = scanner.next_token();
+        assert_eq!(t.t, TokenType::NotEquals);
+        assert_eq!(t.literal, TokenLiteral::Str("!=".to_string()));
+
+        let t = scanner.next_token();
+        assert_eq!(t.t, TokenType::QuotedValue);
+        assert_eq!(t.literal, TokenLiteral::Str("test".to_string()));
+
+        let t = scanner.next_token();
+        assert_eq!(t.t, TokenType::Eof);
+    }
+
+    #[test]
+    fn test_separated_bang_and_equals() {
+        let input = "! =";
+        let mut scanner = Scanner::new(input);
+
         let t = scanner.next_token();
         assert_eq!(t.t, TokenType::Bang);
 
         let t = scanner.next_token();
         assert_eq!(t.t, TokenType::Equals);
 
+        let t = scanner.next_token();
+        assert_eq!(t.t, TokenType::Eof);
+    }
+
+    #[test]
+    fn test_range_operators() {
+        let input = "< <= > >=";
+        let mut scanner = Scanner::new(input);
+
+        let t = scanner.next_token();
+        assert_eq!(t.t, TokenType::Less);
+
+        let t = scanner.next_token();
+        assert_eq!(t.t, TokenType::LessEquals);
+        assert_eq!(t.literal, TokenLiteral::Str("<=".to_string()));
+
+        let t = scanner.next_token();
+        assert_eq!(t.t, TokenType::Greater);
+
+        let t = scanner.next_token();
+        assert_eq!(t.t, TokenType::GreaterEquals);
+        assert_eq!(t.literal, TokenLiteral::Str(">=".to_string()));
+
+        let t = scanner.next_token();
+        assert_eq!(t.t, TokenType::Eof);
+    }
+
+    #[test]
+    fn test_parentheses_and_not() {
+        let input = r#"not (a = "1" or !(b="2"))"#;
+        let mut scanner = Scanner::new(input);
+
+        let expected = [
+            TokenType::Bang,
+            TokenType::LeftParen,
+            TokenType::Identifier,
+            TokenType::Equals,
+            TokenType::QuotedValue,
+            TokenType::Or,
+            TokenType::Bang,
+            TokenType::LeftParen,
+            TokenType::Identifier,
+            TokenType::Equals,
+            TokenType::QuotedValue,
+            TokenType::RightParen,
+            TokenType::RightParen,
+            TokenType::Eof,
+        ];
+        for t in expected {
+            assert_eq!(scanner.next_token().t, t);
+        }
+    }
+
+    #[test]
+    fn test_function_call() {
+        let input = "count(*) sum(price)";
+        let mut scanner = Scanner::new(input);
+
+        let expected = [
+            (TokenType::Identifier, "count"),
+            (TokenType::LeftParen, "("),
+            (TokenType::Star, "*"),
+            (TokenType::RightParen, ")"),
+            (TokenType::Identifier, "sum"),
+            (TokenType::LeftParen, "("),
+            (TokenType::Identifier, "price"),
+            (TokenType::RightParen, ")"),
+        ];
+        for (t, literal) in expected {
+            let token = scanner.next_token();
+            assert_eq!(token.t, t);
+            assert_eq!(token.literal, TokenLiteral::Str(literal.to_string()));
+        }
+        assert_eq!(scanner.next_token().t, TokenType::Eof);
+    }
+
+    #[test]
+    fn test_operator_without_whitespace() {
+        let input = r#"age>="30""#;
+        let mut scanner = Scanner::new(input);
+
+        let t = scanner.next_token();
+        assert_eq!(t.t, TokenType::Identifier);
+        assert_eq!(t.literal, TokenLiteral::Str("age".to_string()));
+
+        let t = scanner.next_token();
+        assert_eq!(t.t, TokenType::GreaterEquals);
+        assert_eq!(t.position, 3);
+
         let t = scanner.next_token();
         assert_eq!(t.t, TokenType::QuotedValue);
-        assert_eq!(t.literal, TokenLiteral::Str("test".to_string()));
+        assert_eq!(t.literal, TokenLiteral::Str("30".to_string()));
 
         let t = scanner.next_token();
         assert_eq!(t.t, TokenType::Eof);
@@ -1,15 +1,37 @@
 use std::str::FromStr;
 
 #[derive(Debug, PartialEq, Eq)]
-pub(crate) enum TokenType {
+pub enum TokenType {
     /// Keywords
     Get,
     Where,
+    Group,
+    By,
+    Having,
+    Order,
+    Asc,
+    Desc,
+    Limit,
+    Offset,
+    Index,
+    Create,
+    On,
+    Using,
+    Is,
+    Null,
 
     /// Operators
     Bang,
     Equals,
+    NotEquals,
+    Less,
+    LessEquals,
+    Greater,
+    GreaterEquals,
     At,
+    LeftParen,
+    RightParen,
+    Star,
 
     /// Conditionals
     And,
@@ -30,9 +52,31 @@ impl FromStr for TokenType {
         match input.to_lowercase().as_str() {
             "get" => Ok(TokenType::Get),
             "where" => Ok(TokenType::Where),
+            "group" => Ok(TokenType::Group),
+            "by" => Ok(TokenType::By),
+            "having" => Ok(TokenType::Having),
+            "order" => Ok(TokenType::Order),
+            "asc" => Ok(TokenType::Asc),
+            "desc" => Ok(TokenType::Desc),
+            "limit" => Ok(TokenType::Limit),
+            "offset" => Ok(TokenType::Offset),
+            "index" => Ok(TokenType::Index),
+            "create" => Ok(TokenType::Create),
+            "on" => Ok(TokenType::On),
+            "using" => Ok(TokenType::Using),
+            "is" => Ok(TokenType::Is),
+            "null" => Ok(TokenType::Null),
             "=" => Ok(TokenType::Equals),
-            "!" => Ok(TokenType::Bang),
+            "!=" => Ok(TokenType::NotEquals),
+            "<" => Ok(TokenType::Less),
+            "<=" => Ok(TokenType::LessEquals),
+            ">" => Ok(TokenType::Greater),
+            ">=" => Ok(TokenType::GreaterEquals),
+            "!" | "not" => Ok(TokenType::Bang),
             "@" => Ok(TokenType::At),
+            "(" => Ok(TokenType::LeftParen),
+            ")" => Ok(TokenType::RightParen),
+            "*" => Ok(TokenType::Star),
             "and" => Ok(TokenType::And),
             "or" => Ok(TokenType::Or),
             _ => Ok(TokenType::Identifier),
@@ -41,11 +85,10 @@ impl FromStr for TokenType {
 }
 
 #[derive(Debug)]
-pub(crate) struct Token {
+pub struct Token {
     pub(crate) t: TokenType,
     pub(crate) literal: TokenLiteral,
     pub(crate) position: usize,
-    lexeme: String,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -53,10 +96,10 @@ pub(crate) enum TokenLiteral {
     Str(String),
 }
 
-impl ToString for TokenLiteral {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for TokenLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            TokenLiteral::Str(s) => s.clone(),
+            TokenLiteral::Str(s) => write!(f, "{}", s),
         }
     }
 }
@@ -67,7 +110,6 @@ impl Token {
             position,
             t,
             literal: TokenLiteral::Str(lexeme),
-            lexeme: "".to_string(),
         }
     }
 }
@@ -0,0 +1,396 @@
+/// Column types. Every column gets a type while being loaded, either inferred from sample of
+/// its values or given by schema file. Values of typed columns are stored in fixed width
+/// encodings, text is stored as it is.
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    io::{Error, ErrorKind},
+    path::Path,
+};
+
+use crate::value::{compare_values, parse_number};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnType {
+    /// 64 bit signed integer
+    Int,
+    /// 64 bit float
+    Float,
+    /// `true` or `false`
+    Bool,
+    /// `YYYY-MM-DD`, stored as days since 1970-01-01
+    Date,
+    Text,
+}
+
+/// Types inference tries, in order. Booleans and integers would parse as later ones too.
+const INFERRED: [ColumnType; 4] = [
+    ColumnType::Bool,
+    ColumnType::Int,
+    ColumnType::Float,
+    ColumnType::Date,
+];
+
+impl ColumnType {
+    /// Every type, position in it is code of the type in snapshots
+    pub(crate) const ALL: [ColumnType; 5] = [
+        ColumnType::Int,
+        ColumnType::Float,
+        ColumnType::Bool,
+        ColumnType::Date,
+        ColumnType::Text,
+    ];
+
+    pub(crate) fn from_name(name: &str) -> Option<ColumnType> {
+        match name.to_lowercase().as_str() {
+            "int" => Some(ColumnType::Int),
+            "float" => Some(ColumnType::Float),
+            "bool" => Some(ColumnType::Bool),
+            "date" => Some(ColumnType::Date),
+            "text" => Some(ColumnType::Text),
+            _ => None,
+        }
+    }
+
+    /// Size of every encoded value, `None` for variable width text.
+    pub(crate) fn width(self) -> Option<usize> {
+        match self {
+            ColumnType::Int | ColumnType::Float => Some(8),
+            ColumnType::Bool => Some(1),
+            ColumnType::Date => Some(4),
+            ColumnType::Text => None,
+        }
+    }
+
+    /// Picks the first type all sampled values are encoded in. Values formatted differently
+    /// from how the type shows them, like numbers with leading zeros (e.g. zip codes), plus
+    /// sign or too many digits, stay text.
+    pub(crate) fn infer<V: AsRef<[u8]>>(values: &[V]) -> ColumnType {
+        if values.is_empty() {
+            return ColumnType::Text;
+        }
+        INFERRED
+            .into_iter()
+            .find(|ty| {
+                values
+                    .iter()
+                    .all(|value| ty.encode(value.as_ref()).is_some())
+            })
+            .unwrap_or(ColumnType::Text)
+    }
+
+    /// Encodes textual value, `None` when it is not a value of this type or would not be
+    /// shown the same way, e.g. `02134` as int or `1.50` as float.
+    pub(crate) fn encode(self, value: &[u8]) -> Option<Vec<u8>> {
+        let encoded = self.parse(value)?;
+        (*self.format(Cow::Borrowed(&encoded)) == *value).then_some(encoded)
+    }
+
+    /// Encodes textual value in any form parsing as value of this type, e.g. `02134` as int
+    /// `2134`. Used for query literals and columns typed by schema.
+    pub(crate) fn parse(self, value: &[u8]) -> Option<Vec<u8>> {
+        let text = str::from_utf8(value).ok()?;
+        match self {
+            ColumnType::Int => Some(text.parse::<i64>().ok()?.to_le_bytes().to_vec()),
+            // Adding zero turns -0 into 0, so that equal numbers encode the same
+            ColumnType::Float => {
+                let number = parse_number(value).filter(|n| n.is_finite())?;
+                Some((number + 0.0).to_le_bytes().to_vec())
+            }
+            ColumnType::Bool => match text.to_lowercase().as_str() {
+                "true" => Some(vec![1]),
+                "false" => Some(vec![0]),
+                _ => None,
+            },
+            ColumnType::Date => Some(parse_date(text)?.to_le_bytes().to_vec()),
+            ColumnType::Text => Some(value.to_vec()),
+        }
+    }
+
+    /// Textual form of encoded value. Text is returned as it is.
+    pub(crate) fn format<'v>(self, value: Cow<'v, [u8]>) -> Cow<'v, [u8]> {
+        let text = match self {
+            ColumnType::Int => decode_int(&value).to_string(),
+            ColumnType::Float => decode_float(&value).to_string(),
+            ColumnType::Bool => (value[0] != 0).to_string(),
+            ColumnType::Date => format_date(decode_date(&value)),
+            ColumnType::Text => return value,
+        };
+        Cow::Owned(text.into_bytes())
+    }
+
+    /// Orders encoded values. Text compares numeric-aware, see `compare_values`.
+    pub(crate) fn compare(self, a: &[u8], b: &[u8]) -> std::cmp::Ordering {
+        match self {
+            ColumnType::Int => decode_int(a).cmp(&decode_int(b)),
+            ColumnType::Float => decode_float(a).total_cmp(&decode_float(b)),
+            ColumnType::Bool => a.cmp(b),
+            ColumnType::Date => decode_date(a).cmp(&decode_date(b)),
+            ColumnType::Text => compare_values(a, b),
+        }
+    }
+}
+
+impl std::fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ColumnType::Int => "int",
+            ColumnType::Float => "float",
+            ColumnType::Bool => "bool",
+            ColumnType::Date => "date",
+            ColumnType::Text => "text",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+pub(crate) fn decode_int(value: &[u8]) -> i64 {
+    i64::from_le_bytes(value.try_into().expect("Invalid int"))
+}
+
+pub(crate) fn decode_float(value: &[u8]) -> f64 {
+    f64::from_le_bytes(value.try_into().expect("Invalid float"))
+}
+
+fn decode_date(value: &[u8]) -> i32 {
+    i32::from_le_bytes(value.try_into().expect("Invalid date"))
+}
+
+/// Days since 1970-01-01 of `YYYY-MM-DD` date.
+fn parse_date(text: &str) -> Option<i32> {
+    let bytes = text.as_bytes();
+    if bytes.len() != 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+        return None;
+    }
+    let number = |range: std::ops::Range<usize>| -> Option<i64> {
+        let part = &text[range];
+        part.bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| part.parse().ok())?
+    };
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    // Days from civil, years start in March so that leap day is the last one
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some((era * 146_097 + day_of_era - 719_468) as i32)
+}
+
+fn format_date(days: i32) -> String {
+    let days = days as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = (month_from_march + 2) % 12 + 1;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Types of columns of a single table, keyed by column name.
+pub type ColumnTypes = BTreeMap<String, ColumnType>;
+
+/// Column types given by `--schema` file, overriding inferred ones. Every line holds
+/// `column: type`, or `table.column: type` for column of single table. Lines starting with
+/// `#` are comments.
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    /// Table (all of them when `None`), column and its type
+    columns: Vec<(Option<String>, String, ColumnType)>,
+}
+
+impl Schema {
+    pub fn read(path: &Path) -> std::io::Result<Schema> {
+        Schema::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub(crate) fn parse(text: &str) -> std::io::Result<Schema> {
+        let mut columns = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = |message: String| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Schema line {}: {}", i + 1, message),
+                )
+            };
+            let (name, ty) = line
+                .split_once(':')
+                .ok_or_else(|| invalid("expected `column: type`".to_string()))?;
+            let ty = ColumnType::from_name(ty.trim())
+                .ok_or_else(|| invalid(format!("unknown type {:?}", ty.trim())))?;
+            let (table, column) = match name.trim().split_once('.') {
+                Some((table, column)) => (Some(table.trim().to_string()), column.trim()),
+                None => (None, name.trim()),
+            };
+            columns.push((table, column.to_string(), ty));
+        }
+        Ok(Schema { columns })
+    }
+
+    /// Types given for columns of table, the ones given for that table take precedence.
+    pub(crate) fn table(&self, name: &str) -> ColumnTypes {
+        let mut types = ColumnTypes::new();
+        for (table, column, ty) in &self.columns {
+            if table.is_none() {
+                types.insert(column.clone(), *ty);
+            }
+        }
+        for (table, column, ty) in &self.columns {
+            if table.as_deref() == Some(name) {
+                types.insert(column.clone(), *ty);
+            }
+        }
+        types
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Ordering;
+
+    fn infer(values: &[&str]) -> ColumnType {
+        ColumnType::infer(values)
+    }
+
+    #[test]
+    fn test_infer() {
+        assert_eq!(infer(&["1", "-20", "300"]), ColumnType::Int);
+        assert_eq!(infer(&["1", "2.5"]), ColumnType::Float);
+        assert_eq!(infer(&["true", "false"]), ColumnType::Bool);
+        assert_eq!(infer(&["true", "FALSE"]), ColumnType::Text);
+        assert_eq!(infer(&["2024-02-29", "1969-12-31"]), ColumnType::Date);
+        assert_eq!(infer(&["1", "abc"]), ColumnType::Text);
+        assert_eq!(infer(&["1", ""]), ColumnType::Text);
+        assert_eq!(infer(&[]), ColumnType::Text);
+    }
+
+    #[test]
+    fn test_infer_keeps_values_shown_differently_as_text() {
+        assert_eq!(infer(&["02134", "10001"]), ColumnType::Text);
+        assert_eq!(infer(&["+5"]), ColumnType::Text);
+        assert_eq!(infer(&[" 5"]), ColumnType::Text);
+        assert_eq!(infer(&["inf"]), ColumnType::Text);
+        assert_eq!(infer(&[".5"]), ColumnType::Text);
+        assert_eq!(infer(&["1.50"]), ColumnType::Text);
+        assert_eq!(infer(&["10.0"]), ColumnType::Text);
+        assert_eq!(infer(&["1e3"]), ColumnType::Text);
+        assert_eq!(infer(&["12345678901234567891"]), ColumnType::Text);
+        assert_eq!(infer(&["0", "-0.5", "10"]), ColumnType::Float);
+    }
+
+    #[test]
+    fn test_parse_accepts_any_form() {
+        let int = |value: i64| Some(value.to_le_bytes().to_vec());
+        assert_eq!(ColumnType::Int.parse(b"02134"), int(2134));
+        assert_eq!(ColumnType::Int.parse(b"+5"), int(5));
+        assert_eq!(ColumnType::Int.encode(b"02134"), None);
+        assert_eq!(ColumnType::Bool.parse(b"TRUE"), Some(vec![1]));
+        assert_eq!(ColumnType::Bool.encode(b"TRUE"), None);
+        assert_eq!(
+            ColumnType::Float.parse(b"1.50"),
+            ColumnType::Float.encode(b"1.5")
+        );
+        assert_eq!(ColumnType::Float.parse(b"inf"), None);
+    }
+
+    #[test]
+    fn test_encoding_round_trip() {
+        let values = [
+            (ColumnType::Int, "-42"),
+            (ColumnType::Float, "2.5"),
+            (ColumnType::Float, "0.1"),
+            (ColumnType::Float, "-1000000"),
+            (ColumnType::Bool, "true"),
+            (ColumnType::Date, "1970-01-01"),
+            (ColumnType::Date, "2000-02-29"),
+            (ColumnType::Date, "1899-12-31"),
+            (ColumnType::Text, "a;b"),
+        ];
+        for (ty, value) in values {
+            let encoded = ty.encode(value.as_bytes()).unwrap();
+            assert_eq!(encoded.len(), ty.width().unwrap_or(value.len()));
+            assert_eq!(&*ty.format(Cow::Owned(encoded)), value.as_bytes());
+        }
+        assert_eq!(
+            ColumnType::Date.encode(b"1970-01-02"),
+            Some(vec![1, 0, 0, 0])
+        );
+    }
+
+    #[test]
+    fn test_encode_invalid_values() {
+        assert_eq!(ColumnType::Int.encode(b"2.5"), None);
+        assert_eq!(ColumnType::Int.encode(b"99999999999999999999"), None);
+        assert_eq!(ColumnType::Float.encode(b"NaN"), None);
+        assert_eq!(ColumnType::Bool.encode(b"yes"), None);
+        assert_eq!(ColumnType::Date.encode(b"2023-02-29"), None);
+        assert_eq!(ColumnType::Date.encode(b"2023-13-01"), None);
+        assert_eq!(ColumnType::Date.encode(b"2023-1-01"), None);
+        assert_eq!(ColumnType::Date.encode(b"+023-01-01"), None);
+    }
+
+    #[test]
+    fn test_compare_by_type() {
+        let compare = |ty: ColumnType, a: &str, b: &str| {
+            ty.compare(
+                &ty.parse(a.as_bytes()).unwrap(),
+                &ty.parse(b.as_bytes()).unwrap(),
+            )
+        };
+        assert_eq!(compare(ColumnType::Int, "-10", "9"), Ordering::Less);
+        assert_eq!(compare(ColumnType::Float, "-0", "0"), Ordering::Equal);
+        assert_eq!(compare(ColumnType::Float, "1e10", "9.5"), Ordering::Greater);
+        assert_eq!(compare(ColumnType::Bool, "false", "TRUE"), Ordering::Less);
+        assert_eq!(
+            compare(ColumnType::Date, "1999-12-31", "2000-01-01"),
+            Ordering::Less
+        );
+        assert_eq!(compare(ColumnType::Text, "9", "10"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_parse_schema() {
+        let schema = Schema::parse(
+            "# zip codes are numbers here\nzip: int\n\norders.zip : text\nprice:FLOAT\n",
+        )
+        .unwrap();
+
+        let people = schema.table("people");
+        assert_eq!(people.get("zip"), Some(&ColumnType::Int));
+        assert_eq!(people.get("price"), Some(&ColumnType::Float));
+        assert_eq!(schema.table("orders").get("zip"), Some(&ColumnType::Text));
+    }
+
+    #[test]
+    fn test_parse_invalid_schema() {
+        let error = Schema::parse("zip: int\nage integer").unwrap_err();
+        assert_eq!(error.to_string(), "Schema line 2: expected `column: type`");
+
+        let error = Schema::parse("age: integer").unwrap_err();
+        assert_eq!(error.to_string(), "Schema line 1: unknown type \"integer\"");
+    }
+}
@@ -0,0 +1,701 @@
+/// Snapshots of loaded working sets, so that large files do not have to be parsed on every
+/// start. Snapshot is written next to the source file and rebuilt once the file changes.
+///
+/// Layout, all numbers little endian:
+/// - magic, version, length of source description, source description and its CRC32
+/// - column names with their positions
+/// - column types
+/// - blocks of every column heap, see [`BlockStore::write_to`]
+/// - blocks and value pointers of dictionary of every column, flag of its presence first
+/// - pointers of text values of every row
+/// - ids of rows holding null, per column
+/// - zones of blocks of every column, flag of their presence first
+/// - CRC32 of everything after the header
+use crate::{
+    index::{
+        bitmap::Bitmap,
+        heap::{BlockOffset, Storage},
+        zone::ZoneMap,
+    },
+    mem::{ColumnsWorkingSet, Dialect, Dictionary, index_heap_columnar},
+    schema::{ColumnType, ColumnTypes},
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter, Error, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+const MAGIC: &[u8; 8] = b"CSVRSNAP";
+/// Bumped whenever layout or the way values are typed changes, snapshots of other versions
+/// are rebuilt.
+const VERSION: u32 = 6;
+/// Sanity limit for lengths read from header
+const MAX_HEADER_SIZE: usize = 1 << 20;
+const ROW_POINTER_SIZE: usize = size_of::<u64>() + size_of::<u32>();
+
+/// `orders.csv` is snapshotted into `orders.csv.snapshot`.
+pub(crate) fn snapshot_path(source: &Path) -> PathBuf {
+    let mut name = source.as_os_str().to_owned();
+    name.push(".snapshot");
+    name.into()
+}
+
+/// Opens up to date snapshot of CSV file. Otherwise loads the file and writes new snapshot,
+/// failing to write it only costs parsing the file again next time.
+pub(crate) fn load_or_build(
+    path: &Path,
+    dialect: &Dialect,
+    names: Option<&[String]>,
+    schema: &ColumnTypes,
+    storage: Storage,
+) -> std::io::Result<ColumnsWorkingSet> {
+    // Taken before parsing, file changed meanwhile leaves the snapshot stale
+    let source = Source::of(path, dialect, names, schema)?;
+    let snapshot = snapshot_path(path);
+
+    match read(&snapshot, &source, storage) {
+        Ok(Some(set)) => return Ok(set),
+        Ok(None) => {}
+        Err(e) => eprintln!("Ignoring snapshot {}: {}", snapshot.display(), e),
+    }
+
+    let set = index_heap_columnar(path.to_path_buf(), dialect, names, schema, storage)?;
+    if let Err(e) = write(&set, &snapshot, &source) {
+        eprintln!("Could not write snapshot {}: {}", snapshot.display(), e);
+    }
+    Ok(set)
+}
+
+/// What snapshot was made from, it is stale once any of it changes.
+#[derive(Debug, PartialEq)]
+struct Source {
+    size: u64,
+    /// Modification time since Unix epoch
+    modified_secs: u64,
+    modified_nanos: u32,
+    /// Dialect, column names and types the file was parsed with
+    settings: String,
+}
+
+impl Source {
+    fn of(
+        path: &Path,
+        dialect: &Dialect,
+        names: Option<&[String]>,
+        schema: &ColumnTypes,
+    ) -> std::io::Result<Source> {
+        let metadata = std::fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(Source {
+            size: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+            settings: format!("{:?} {:?} {:?}", dialect, names, schema),
+        })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&self.size.to_le_bytes());
+        bytes.extend_from_slice(&self.modified_secs.to_le_bytes());
+        bytes.extend_from_slice(&self.modified_nanos.to_le_bytes());
+        bytes.extend_from_slice(self.settings.as_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Source> {
+        Some(Source {
+            size: u64::from_le_bytes(bytes.get(..8)?.try_into().ok()?),
+            modified_secs: u64::from_le_bytes(bytes.get(8..16)?.try_into().ok()?),
+            modified_nanos: u32::from_le_bytes(bytes.get(16..20)?.try_into().ok()?),
+            settings: String::from_utf8(bytes[20..].to_vec()).ok()?,
+        })
+    }
+}
+
+/// Reader or writer computing CRC32 of all bytes that pass through it.
+struct Checksummed<T> {
+    inner: T,
+    hasher: crc32fast::Hasher,
+}
+
+impl<T> Checksummed<T> {
+    fn new(inner: T) -> Self {
+        Checksummed {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for Checksummed<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: Read> Read for Checksummed<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+fn corrupted(what: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("{} is corrupted", what))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> std::io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> std::io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Writes value with its length.
+fn write_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> std::io::Result<()> {
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+fn read_bytes<R: Read>(reader: &mut R, what: &str) -> std::io::Result<Vec<u8>> {
+    let length = read_u32(reader)? as usize;
+    if length > MAX_HEADER_SIZE {
+        return Err(corrupted(what));
+    }
+    let mut bytes = vec![0u8; length];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn write_pointer<W: Write>(writer: &mut W, pointer: (usize, BlockOffset)) -> std::io::Result<()> {
+    let (block_id, offset) = pointer;
+    writer.write_all(&(block_id as u64).to_le_bytes())?;
+    writer.write_all(&offset.to_bits().to_le_bytes())
+}
+
+fn decode_pointer(bytes: &[u8]) -> (usize, BlockOffset) {
+    let (block_id, offset) = bytes.split_at(size_of::<u64>());
+    (
+        u64::from_le_bytes(block_id.try_into().unwrap()) as usize,
+        BlockOffset::from_bits(u32::from_le_bytes(offset.try_into().unwrap())),
+    )
+}
+
+/// Writes snapshot into temporary file first, so that readers never see half written one.
+fn write(set: &ColumnsWorkingSet, path: &Path, source: &Source) -> std::io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+
+    let mut writer = BufWriter::new(File::create(&temporary)?);
+    let header = source.encode();
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&(header.len() as u32).to_le_bytes())?;
+    writer.write_all(&header)?;
+    writer.write_all(&crc32fast::hash(&header).to_le_bytes())?;
+
+    let mut body = Checksummed::new(writer);
+    body.write_all(&(set.columns.len() as u32).to_le_bytes())?;
+    for (name, position) in &set.columns {
+        body.write_all(&(name.len() as u32).to_le_bytes())?;
+        body.write_all(name.as_bytes())?;
+        body.write_all(&(*position as u32).to_le_bytes())?;
+    }
+
+    for ty in &set.types {
+        let code = ColumnType::ALL.iter().position(|t| t == ty).unwrap();
+        body.write_all(&[code as u8])?;
+    }
+
+    body.write_all(&(set.data.len() as u32).to_le_bytes())?;
+    for heap in &set.data {
+        heap.write_to(&mut body)?;
+    }
+
+    for dictionary in &set.dictionaries {
+        let Some(dictionary) = dictionary else {
+            body.write_all(&[0])?;
+            continue;
+        };
+        body.write_all(&[1])?;
+        dictionary.heap.write_to(&mut body)?;
+        body.write_all(&(dictionary.values.len() as u32).to_le_bytes())?;
+        for pointer in &dictionary.values {
+            write_pointer(&mut body, *pointer)?;
+        }
+    }
+
+    body.write_all(&(set.rows.len() as u64).to_le_bytes())?;
+    for row in &set.rows {
+        body.write_all(&(row.len() as u32).to_le_bytes())?;
+        for pointer in row {
+            write_pointer(&mut body, *pointer)?;
+        }
+    }
+
+    for nulls in &set.nulls {
+        body.write_all(&(nulls.len() as u64).to_le_bytes())?;
+        for row in nulls.iter() {
+            body.write_all(&row.to_le_bytes())?;
+        }
+    }
+
+    for zones in &set.zones {
+        let Some(zones) = zones else {
+            body.write_all(&[0])?;
+            continue;
+        };
+        body.write_all(&[1])?;
+        body.write_all(&(zones.block_count() as u32).to_le_bytes())?;
+        for block_id in 0..zones.block_count() {
+            let Some((min, max)) = zones.zone(block_id) else {
+                body.write_all(&[0])?;
+                continue;
+            };
+            body.write_all(&[1])?;
+            write_bytes(&mut body, min)?;
+            write_bytes(&mut body, max)?;
+        }
+    }
+
+    let checksum = body.hasher.finalize();
+    let mut writer = body.inner;
+    writer.write_all(&checksum.to_le_bytes())?;
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+
+    std::fs::rename(temporary, path)
+}
+
+/// Reads snapshot made from given source. Missing or stale snapshot and one written by other
+/// version give `None`, damaged one is an error.
+fn read(
+    path: &Path,
+    source: &Source,
+    storage: Storage,
+) -> std::io::Result<Option<ColumnsWorkingSet>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let mut reader = BufReader::new(file);
+
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(Error::new(ErrorKind::InvalidData, "Not a snapshot"));
+    }
+    if read_u32(&mut reader)? != VERSION {
+        return Ok(None);
+    }
+
+    let length = read_u32(&mut reader)? as usize;
+    if length > MAX_HEADER_SIZE {
+        return Err(corrupted("Header"));
+    }
+    let mut header = vec![0u8; length];
+    reader.read_exact(&mut header)?;
+    if read_u32(&mut reader)? != crc32fast::hash(&header) {
+        return Err(corrupted("Header"));
+    }
+    if Source::decode(&header).ok_or_else(|| corrupted("Header"))? != *source {
+        return Ok(None);
+    }
+
+    let mut body = Checksummed::new(reader);
+    let mut columns = HashMap::new();
+    for _ in 0..read_u32(&mut body)? {
+        let length = read_u32(&mut body)? as usize;
+        if length > MAX_HEADER_SIZE {
+            return Err(corrupted("Column name"));
+        }
+        let mut name = vec![0u8; length];
+        body.read_exact(&mut name)?;
+        let name = String::from_utf8(name).map_err(|_| corrupted("Column name"))?;
+        columns.insert(name, read_u32(&mut body)? as usize);
+    }
+
+    let mut types = Vec::with_capacity(columns.len());
+    for _ in 0..columns.len() {
+        let mut code = [0u8];
+        body.read_exact(&mut code)?;
+        let ty = ColumnType::ALL.get(code[0] as usize);
+        types.push(*ty.ok_or_else(|| corrupted("Column type"))?);
+    }
+
+    let heaps = read_u32(&mut body)? as usize;
+    if heaps != types.len() {
+        return Err(corrupted("Heaps"));
+    }
+    let mut data = Vec::with_capacity(heaps);
+    for _ in 0..heaps {
+        let mut heap = storage.create(heaps)?;
+        heap.read_blocks(&mut body)?;
+        data.push(heap);
+    }
+
+    let mut dictionaries = Vec::with_capacity(heaps);
+    let mut pointer = [0u8; ROW_POINTER_SIZE];
+    for _ in 0..heaps {
+        let mut flag = [0u8];
+        body.read_exact(&mut flag)?;
+        if flag[0] == 0 {
+            dictionaries.push(None);
+            continue;
+        }
+
+        let mut heap = storage.create(heaps)?;
+        heap.read_blocks(&mut body)?;
+        let count = read_u32(&mut body)? as usize;
+        if count > u16::MAX as usize + 1 {
+            return Err(corrupted("Dictionary"));
+        }
+        let mut values = Vec::with_capacity(count);
+        for _ in 0..count {
+            body.read_exact(&mut pointer)?;
+            values.push(decode_pointer(&pointer));
+        }
+        dictionaries.push(Some(Dictionary { heap, values }));
+    }
+
+    let count = read_u64(&mut body)? as usize;
+    let texts = types
+        .iter()
+        .zip(&dictionaries)
+        .filter(|(ty, dictionary)| ty.width().is_none() && dictionary.is_none())
+        .count();
+    let mut rows = Vec::with_capacity(count.min(MAX_HEADER_SIZE));
+    let mut buffer = vec![];
+    for _ in 0..count {
+        let width = read_u32(&mut body)? as usize;
+        if width != texts {
+            return Err(corrupted("Row"));
+        }
+        buffer.resize(width * ROW_POINTER_SIZE, 0);
+        body.read_exact(&mut buffer)?;
+
+        let row = buffer
+            .chunks_exact(ROW_POINTER_SIZE)
+            .map(decode_pointer)
+            .collect();
+        rows.push(row);
+    }
+
+    let mut nulls = Vec::with_capacity(types.len());
+    for _ in 0..types.len() {
+        let mut column = Bitmap::new();
+        for _ in 0..read_u64(&mut body)? {
+            let row = read_u32(&mut body)?;
+            if row as usize >= count {
+                return Err(corrupted("Nulls"));
+            }
+            column.insert(row);
+        }
+        nulls.push(column);
+    }
+
+    let mut zones = Vec::with_capacity(types.len());
+    let mut flag = [0u8];
+    for ty in &types {
+        body.read_exact(&mut flag)?;
+        if flag[0] == 0 {
+            zones.push(None);
+            continue;
+        }
+
+        let mut column = ZoneMap::new(*ty);
+        for _ in 0..read_u32(&mut body)? {
+            body.read_exact(&mut flag)?;
+            column.zones.push(match flag[0] {
+                0 => None,
+                _ => Some((
+                    read_bytes(&mut body, "Zone")?,
+                    read_bytes(&mut body, "Zone")?,
+                )),
+            });
+        }
+        zones.push(Some(column));
+    }
+
+    let checksum = body.hasher.finalize();
+    if read_u32(&mut body.inner)? != checksum {
+        return Err(corrupted("Snapshot"));
+    }
+
+    Ok(Some(ColumnsWorkingSet::new(
+        columns,
+        types,
+        data,
+        rows,
+        nulls,
+        dictionaries,
+        zones,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+    use tempfile::TempDir;
+
+    fn csv(dir: &TempDir, content: &str) -> PathBuf {
+        let path = dir.path().join("people.csv");
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    /// Values of every row as text, `null` for nulls.
+    fn values(set: &ColumnsWorkingSet) -> Vec<Vec<String>> {
+        (0..set.rows.len())
+            .map(|row| {
+                (0..set.types.len())
+                    .map(|col| match set.text(row, col).unwrap() {
+                        Some(text) => String::from_utf8(text.into()).unwrap(),
+                        None => "null".to_string(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn source(path: &Path) -> Source {
+        Source::of(path, &Dialect::default(), None, &ColumnTypes::new()).unwrap()
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() -> std::io::Result<()> {
+        let dir = TempDir::new()?;
+        let long = "x".repeat(20_000);
+        let path = csv(
+            &dir,
+            &format!("name;age;bio\nAlice;25;{}\nBob;30;short\nCarol;NA;\n", long),
+        );
+
+        let loaded = load_or_build(
+            &path,
+            &Dialect::default(),
+            None,
+            &ColumnTypes::new(),
+            Storage::Memory,
+        )?;
+        assert!(snapshot_path(&path).exists());
+
+        let restored = read(&snapshot_path(&path), &source(&path), Storage::Memory)?.unwrap();
+        assert_eq!(restored.columns, loaded.columns);
+        assert_eq!(restored.types, loaded.types);
+        assert_eq!(restored.types[1], ColumnType::Int);
+        assert_eq!(restored.rows, loaded.rows);
+        assert_eq!(restored.nulls, loaded.nulls);
+        let zones = |set: &ColumnsWorkingSet| {
+            let zones = set
+                .zones
+                .iter()
+                .map(|zones| zones.as_ref().map(|z| z.zones.clone()));
+            zones.collect::<Vec<_>>()
+        };
+        assert_eq!(zones(&restored), zones(&loaded));
+        assert!(restored.zones[1].as_ref().unwrap().zone(0).is_some());
+        assert_eq!(
+            values(&restored),
+            [
+                ["Alice", "25", &long],
+                ["Bob", "30", "short"],
+                ["Carol", "null", "null"]
+            ]
+        );
+
+        let reopened = load_or_build(
+            &path,
+            &Dialect::default(),
+            None,
+            &ColumnTypes::new(),
+            Storage::Memory,
+        )?;
+        assert_eq!(values(&reopened), values(&loaded));
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_into_paged_storage() -> std::io::Result<()> {
+        let dir = TempDir::new()?;
+        let long = "x".repeat(20_000);
+        let path = csv(&dir, &format!("name;bio\nAlice;{}\nBob;short\n", long));
+        let loaded = load_or_build(
+            &path,
+            &Dialect::default(),
+            None,
+            &ColumnTypes::new(),
+            Storage::Memory,
+        )?;
+
+        let restored = read(&snapshot_path(&path), &source(&path), Storage::Paged(2))?.unwrap();
+        assert_eq!(values(&restored), values(&loaded));
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_keeps_dictionaries() -> std::io::Result<()> {
+        let dir = TempDir::new()?;
+        let mut content = "name;country\n".to_string();
+        for i in 0..20 {
+            content.push_str(&format!("n{};{}\n", i, ["US", "DE", ""][i % 3]));
+        }
+        let path = csv(&dir, &content);
+        let loaded = load_or_build(
+            &path,
+            &Dialect::default(),
+            None,
+            &ColumnTypes::new(),
+            Storage::Memory,
+        )?;
+        assert!(loaded.dictionaries[1].is_some());
+
+        let restored = read(&snapshot_path(&path), &source(&path), Storage::Memory)?.unwrap();
+        let dictionary = restored.dictionaries[1].as_ref().unwrap();
+        assert_eq!(
+            dictionary.values().collect::<std::io::Result<Vec<_>>>()?,
+            [&b"US"[..], b"DE"]
+        );
+        assert!(restored.dictionaries[0].is_none());
+        assert_eq!(restored.rows, loaded.rows);
+        assert_eq!(values(&restored), values(&loaded));
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_is_stale_when_source_changes() -> std::io::Result<()> {
+        let dir = TempDir::new()?;
+        let path = csv(&dir, "name;age\nAlice;25\n");
+        load_or_build(
+            &path,
+            &Dialect::default(),
+            None,
+            &ColumnTypes::new(),
+            Storage::Memory,
+        )?;
+        let snapshot = snapshot_path(&path);
+
+        // Same size, only modification time differs
+        let file = File::options().write(true).open(&path)?;
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))?;
+        assert!(read(&snapshot, &source(&path), Storage::Memory)?.is_none());
+
+        csv(&dir, "name;age\nAlice;25\nBob;30\n");
+        assert!(read(&snapshot, &source(&path), Storage::Memory)?.is_none());
+        let reloaded = load_or_build(
+            &path,
+            &Dialect::default(),
+            None,
+            &ColumnTypes::new(),
+            Storage::Memory,
+        )?;
+        assert_eq!(values(&reloaded), [["Alice", "25"], ["Bob", "30"]]);
+        assert!(read(&snapshot, &source(&path), Storage::Memory)?.is_some());
+
+        // Parsed with other settings
+        let names = ["first".to_string()];
+        let renamed = Source::of(
+            &path,
+            &Dialect::default(),
+            Some(&names),
+            &ColumnTypes::new(),
+        )?;
+        assert!(read(&snapshot, &renamed, Storage::Memory)?.is_none());
+        let schema = ColumnTypes::from([("name".to_string(), ColumnType::Int)]);
+        let retyped = Source::of(&path, &Dialect::default(), None, &schema)?;
+        assert!(read(&snapshot, &retyped, Storage::Memory)?.is_none());
+        let dialect = Dialect {
+            nulls: vec![],
+            ..Dialect::default()
+        };
+        let without_nulls = Source::of(&path, &dialect, None, &ColumnTypes::new())?;
+        assert!(read(&snapshot, &without_nulls, Storage::Memory)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_of_other_version_is_rebuilt() -> std::io::Result<()> {
+        let dir = TempDir::new()?;
+        let path = csv(&dir, "name;age\nAlice;25\n");
+        load_or_build(
+            &path,
+            &Dialect::default(),
+            None,
+            &ColumnTypes::new(),
+            Storage::Memory,
+        )?;
+        let snapshot = snapshot_path(&path);
+
+        let mut bytes = std::fs::read(&snapshot)?;
+        bytes[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        std::fs::write(&snapshot, &bytes)?;
+
+        assert!(read(&snapshot, &source(&path), Storage::Memory)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupted_snapshot_is_detected_and_rebuilt() -> std::io::Result<()> {
+        let dir = TempDir::new()?;
+        let path = csv(&dir, "name;age\nAlice;25\n");
+        load_or_build(
+            &path,
+            &Dialect::default(),
+            None,
+            &ColumnTypes::new(),
+            Storage::Memory,
+        )?;
+        let snapshot = snapshot_path(&path);
+        let original = std::fs::read(&snapshot)?;
+
+        // Header, block data and row pointers
+        for position in [20, original.len() / 2, original.len() - 8] {
+            let mut bytes = original.clone();
+            bytes[position] ^= 0xff;
+            std::fs::write(&snapshot, &bytes)?;
+
+            let error = read(&snapshot, &source(&path), Storage::Memory)
+                .err()
+                .unwrap();
+            assert_eq!(error.kind(), ErrorKind::InvalidData, "{}", position);
+        }
+
+        std::fs::write(&snapshot, &original[..original.len() - 1])?;
+        assert!(read(&snapshot, &source(&path), Storage::Memory).is_err());
+
+        let reloaded = load_or_build(
+            &path,
+            &Dialect::default(),
+            None,
+            &ColumnTypes::new(),
+            Storage::Memory,
+        )?;
+        assert_eq!(values(&reloaded), [["Alice", "25"]]);
+        assert!(read(&snapshot, &source(&path), Storage::Memory)?.is_some());
+        Ok(())
+    }
+}
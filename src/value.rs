@@ -0,0 +1,168 @@
+/// Helpers for interpreting raw column bytes.
+use std::cmp::Ordering;
+
+/// Parses value as a number, if it looks like one.
+pub(crate) fn parse_number(value: &[u8]) -> Option<f64> {
+    str::from_utf8(value)
+        .ok()?
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|n| !n.is_nan())
+}
+
+/// Total order over raw values. Numbers compare numerically and sort before text,
+/// text compares bytewise.
+pub(crate) fn compare_values(a: &[u8], b: &[u8]) -> Ordering {
+    match (parse_number(a), parse_number(b)) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.cmp(b),
+    }
+}
+
+/// First byte of index key of a number.
+pub(crate) const NUMBER_KEY: u8 = 0;
+/// First byte of index key of a text. Texts sort after all numbers, as in `compare_values`.
+pub(crate) const TEXT_KEY: u8 = 1;
+
+/// Encodes value so that keys compare bytewise the same way as values compare with
+/// `compare_values`. Numbers with different text (`10` and `10.0`) share the same key.
+pub(crate) fn index_key(value: &[u8]) -> Vec<u8> {
+    match parse_number(value) {
+        Some(n) => {
+            // Flip sign bit of positives and all bits of negatives, so that bytes follow
+            // `f64::total_cmp`
+            let bits = n.to_bits();
+            let bits = if bits >> 63 == 1 {
+                !bits
+            } else {
+                bits | (1 << 63)
+            };
+            let mut key = Vec::with_capacity(9);
+            key.push(NUMBER_KEY);
+            key.extend_from_slice(&bits.to_be_bytes());
+            key
+        }
+        None => {
+            let mut key = Vec::with_capacity(value.len() + 1);
+            key.push(TEXT_KEY);
+            key.extend_from_slice(value);
+            key
+        }
+    }
+}
+
+/// Appends index key of value as next component of tuple key. Zero bytes are escaped as
+/// `0x00 0x01` and component ends with `0x00 0x00`, so no component is a prefix of another
+/// and tuple keys compare bytewise column by column.
+pub(crate) fn append_key(key: &mut Vec<u8>, value: &[u8]) {
+    for byte in index_key(value) {
+        key.push(byte);
+        if byte == 0 {
+            key.push(1);
+        }
+    }
+    key.extend_from_slice(&[0, 0]);
+}
+
+/// Key of tuple of values, e.g. of row in composite index.
+pub(crate) fn tuple_key<V: AsRef<[u8]>>(values: &[V]) -> Vec<u8> {
+    let mut key = vec![];
+    for value in values {
+        append_key(&mut key, value.as_ref());
+    }
+    key
+}
+
+/// Smallest key greater than all keys starting with given tuple key.
+pub(crate) fn tuple_key_end(key: &[u8]) -> Vec<u8> {
+    let mut end = key.to_vec();
+    *end.last_mut().expect("Empty tuple key") = 1;
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number(b"42"), Some(42.0));
+        assert_eq!(parse_number(b" -1.5 "), Some(-1.5));
+        assert_eq!(parse_number(b"abc"), None);
+        assert_eq!(parse_number(b""), None);
+        assert_eq!(parse_number(b"NaN"), None);
+    }
+
+    #[test]
+    fn test_compare_numbers_numerically() {
+        assert_eq!(compare_values(b"9", b"10"), Ordering::Less);
+        assert_eq!(compare_values(b"10.0", b"10"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_numbers_sort_before_text() {
+        assert_eq!(compare_values(b"100", b"abc"), Ordering::Less);
+        assert_eq!(compare_values(b"abc", b"1"), Ordering::Greater);
+        assert_eq!(compare_values(b"abc", b"abd"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_index_key_follows_compare_values() {
+        let values: [&[u8]; 12] = [
+            b"-1e10", b"-2.5", b"-0", b"0", b"1", b"9", b"10", b"10.5", b"1e300", b"", b"abc",
+            b"abd",
+        ];
+        for a in values {
+            for b in values {
+                assert_eq!(
+                    index_key(a).cmp(&index_key(b)),
+                    compare_values(a, b),
+                    "{:?} {:?}",
+                    str::from_utf8(a),
+                    str::from_utf8(b)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_index_key_of_equal_numbers() {
+        assert_eq!(index_key(b"10"), index_key(b"10.0"));
+        assert_eq!(index_key(b"abc")[0], TEXT_KEY);
+        assert_eq!(index_key(b"1")[0], NUMBER_KEY);
+    }
+
+    #[test]
+    fn test_tuple_key_compares_column_by_column() {
+        let tuples: [&[&str]; 8] = [
+            &["-1", "z"],
+            &["9", ""],
+            &["9", "a"],
+            &["10", "a"],
+            &["a", "1"],
+            &["a", "b"],
+            &["ab", ""],
+            &["b", "a"],
+        ];
+        for pair in tuples.windows(2) {
+            assert!(tuple_key(pair[0]) < tuple_key(pair[1]), "{:?}", pair);
+        }
+        assert_eq!(tuple_key(&["10", "x"]), tuple_key(&["10.0", "x"]));
+    }
+
+    #[test]
+    fn test_tuple_key_end() {
+        let prefix = tuple_key(&["a"]);
+        let end = tuple_key_end(&prefix);
+
+        for inside in [tuple_key(&["a", "1"]), tuple_key(&["a", "zzz"])] {
+            assert!(prefix <= inside && inside < end);
+        }
+        for outside in [tuple_key(&[""]), tuple_key(&["a\0"]), tuple_key(&["ab"])] {
+            assert!(outside < prefix || outside >= end, "{:?}", outside);
+        }
+    }
+}